@@ -25,6 +25,8 @@ async fn main() {
     );
     animation.add_animation(0, 3, None, 15f32, MooseAnimationIdentifier::Run);
     animation.add_animation(4, 9, Some(7), 13f32, MooseAnimationIdentifier::Sleep);
+    // blend run and sleep into each other instead of snapping
+    animation.player.crossfade_duration = 0.15f32;
     let camera = Camera2D {
         zoom: vec2(1. / GAME_SIZE.x * 2., 1. / GAME_SIZE.y * 2.),
         target: vec2(0.0, 0.0),
@@ -37,7 +39,7 @@ async fn main() {
 
         // change animation
         if is_key_pressed(KeyCode::Space) {
-            let next_state = match animation.current_animation {
+            let next_state = match animation.player.current_animation {
                 MooseAnimationIdentifier::Run => MooseAnimationIdentifier::Sleep,
                 MooseAnimationIdentifier::Sleep => MooseAnimationIdentifier::Run,
             };
@@ -0,0 +1,60 @@
+use macroquad::prelude::*;
+use macroquad_tantan_toolbox::animation::aseprite::*;
+
+const GAME_SIZE: Vec2 = Vec2::new(64f32, 64f32);
+
+#[macroquad::main("aseprite")]
+async fn main() {
+    let mut animation = load_aseprite_animation("examples/resources/moose.json")
+        .await
+        .unwrap();
+
+    let game_render_target = render_target(GAME_SIZE.x as u32, GAME_SIZE.y as u32);
+    game_render_target.texture.set_filter(FilterMode::Nearest);
+
+    let camera = Camera2D {
+        zoom: vec2(1. / GAME_SIZE.x * 2., 1. / GAME_SIZE.y * 2.),
+        target: vec2(0.0, 0.0),
+        render_target: Some(game_render_target),
+        ..Default::default()
+    };
+    loop {
+        set_camera(&camera);
+        clear_background(BLUE);
+
+        // change animation, clips are named after the aseprite tags
+        if is_key_pressed(KeyCode::Space) {
+            let next_state = match animation.player.current_animation.as_str() {
+                "run" => "sleep",
+                _ => "run",
+            };
+            animation.play_animation(next_state.to_string());
+        }
+
+        animation.update(get_frame_time());
+        animation.draw(&vec2(0f32, 0f32), false);
+
+        set_default_camera();
+        clear_background(BLUE);
+        // draw game
+        draw_texture_ex(
+            game_render_target.texture,
+            0.,
+            0.,
+            WHITE,
+            DrawTextureParams {
+                dest_size: Some(vec2(screen_width(), screen_height())),
+                ..Default::default()
+            },
+        );
+        draw_text(
+            "tap space to change animation",
+            screen_width() * 0.5f32 - 100f32,
+            40f32,
+            30f32,
+            BLACK,
+        );
+
+        next_frame().await
+    }
+}
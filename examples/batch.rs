@@ -0,0 +1,62 @@
+use macroquad::prelude::*;
+use macroquad_tantan_toolbox::animation::batch::*;
+use macroquad_tantan_toolbox::animation::*;
+use std::sync::Arc;
+
+#[derive(std::hash::Hash, Eq, PartialEq)]
+enum MooseAnimationIdentifier {
+    Run,
+    Sleep,
+}
+
+const MOOSE_COUNT: usize = 1000;
+
+#[macroquad::main("batch")]
+async fn main() {
+    let texture: Texture2D = load_texture("examples/resources/moose.png").await.unwrap();
+    texture.set_filter(FilterMode::Nearest);
+
+    let mut set = AnimationSet::new(10f32, 1f32, texture);
+    set.add_animation(0, 3, None, 15f32, MooseAnimationIdentifier::Run);
+    set.add_animation(4, 9, Some(7), 13f32, MooseAnimationIdentifier::Sleep);
+    let set = Arc::new(set);
+
+    let mut mooses: Vec<(Vec2, AnimationInstance<MooseAnimationIdentifier>)> = (0..MOOSE_COUNT)
+        .map(|i| {
+            let identifier = if i % 2 == 0 {
+                MooseAnimationIdentifier::Run
+            } else {
+                MooseAnimationIdentifier::Sleep
+            };
+            let mut animation = AnimationInstance::from_set(set.clone(), identifier);
+            animation.player.speed = rand::gen_range(0.5f32, 1.5f32);
+            let pos = vec2(
+                rand::gen_range(0f32, screen_width()),
+                rand::gen_range(0f32, screen_height()),
+            );
+            (pos, animation)
+        })
+        .collect();
+
+    // lower mooses overlap the ones behind them
+    let mut batch = SpriteBatch::new(SpriteOrder::YSort);
+    loop {
+        clear_background(BLUE);
+
+        for (pos, animation) in mooses.iter_mut() {
+            animation.update(get_frame_time());
+            animation.draw_batched(&mut batch, pos, DrawParam::default());
+        }
+        batch.draw();
+
+        draw_text(
+            &format!("{} mooses, {} fps", MOOSE_COUNT, get_fps()),
+            20f32,
+            40f32,
+            30f32,
+            BLACK,
+        );
+
+        next_frame().await
+    }
+}
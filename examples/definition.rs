@@ -0,0 +1,66 @@
+use macroquad::prelude::*;
+use macroquad_tantan_toolbox::animation::definition::*;
+
+const ANIMATION_PATH: &str = "examples/resources/moose_animation.json";
+const GAME_SIZE: Vec2 = Vec2::new(64f32, 64f32);
+
+#[macroquad::main("definition")]
+async fn main() {
+    let mut animation = load_animation_definition(ANIMATION_PATH).await.unwrap();
+    // edit the fps or frames in the json while the example runs
+    let mut reloader = AnimationReloader::new(ANIMATION_PATH);
+
+    let game_render_target = render_target(GAME_SIZE.x as u32, GAME_SIZE.y as u32);
+    game_render_target.texture.set_filter(FilterMode::Nearest);
+
+    let camera = Camera2D {
+        zoom: vec2(1. / GAME_SIZE.x * 2., 1. / GAME_SIZE.y * 2.),
+        target: vec2(0.0, 0.0),
+        render_target: Some(game_render_target),
+        ..Default::default()
+    };
+    loop {
+        set_camera(&camera);
+        clear_background(BLUE);
+
+        // change animation, clips are named in the definition file
+        if is_key_pressed(KeyCode::Space) {
+            let next_state = match animation.player.current_animation.as_str() {
+                "run" => "sleep",
+                _ => "run",
+            };
+            animation.play_animation(next_state.to_string());
+        }
+
+        match reloader.poll(&mut animation) {
+            Ok(true) => println!("reloaded {}", ANIMATION_PATH),
+            Ok(false) => {}
+            Err(err) => println!("{}", err),
+        }
+        animation.update(get_frame_time());
+        animation.draw(&vec2(0f32, 0f32), false);
+
+        set_default_camera();
+        clear_background(BLUE);
+        // draw game
+        draw_texture_ex(
+            game_render_target.texture,
+            0.,
+            0.,
+            WHITE,
+            DrawTextureParams {
+                dest_size: Some(vec2(screen_width(), screen_height())),
+                ..Default::default()
+            },
+        );
+        draw_text(
+            "tap space to change animation",
+            screen_width() * 0.5f32 - 100f32,
+            40f32,
+            30f32,
+            BLACK,
+        );
+
+        next_frame().await
+    }
+}
@@ -0,0 +1,54 @@
+use macroquad::prelude::*;
+use macroquad_tantan_toolbox::embedded_resources;
+use macroquad_tantan_toolbox::resources::*;
+
+// the moose is compiled into the executable, run it from any directory
+
+#[derive(Hash, Eq, Clone, Debug, Copy, PartialEq)]
+pub enum TextureIdentifier {
+    Moose,
+}
+
+pub struct TextureResources {
+    moose: Texture2D,
+}
+
+// the same code works with DefaultFactory, only the factory type changes
+impl<F: ResourceFactory<Texture2D>> Resources<TextureIdentifier, Texture2D, F>
+    for TextureResources
+{
+    fn build(builder: &mut ResourceBuilder<TextureIdentifier, Self, Texture2D, F>) -> Self {
+        Self {
+            moose: builder.get_or_panic(TextureIdentifier::Moose),
+        }
+    }
+}
+
+#[macroquad::main("embedded")]
+async fn main() {
+    let mut resource_builder =
+        ResourceBuilder::<TextureIdentifier, TextureResources, Texture2D, EmbeddedFactory>::new(
+            embedded_resources![(TextureIdentifier::Moose, "examples/resources/moose.png")],
+        );
+    while !resource_builder
+        .load_next()
+        .await
+        .unwrap_or_else(|err| panic!("{}", err))
+    {}
+    let resources = resource_builder.build();
+
+    loop {
+        clear_background(BLUE);
+        draw_texture_ex(
+            resources.moose,
+            20f32,
+            20f32,
+            WHITE,
+            DrawTextureParams {
+                dest_size: Some(vec2(resources.moose.width(), resources.moose.height()) * 4f32),
+                ..Default::default()
+            },
+        );
+        next_frame().await
+    }
+}
@@ -77,52 +77,40 @@ impl LoadingState {
     }
 }
 
-#[derive(Hash, Eq, Clone, Debug, Copy, PartialEq)]
-pub enum TextureIdentifier {
-    Player,
-    Moose,
-}
-
-pub struct TextureResources {
+// generates ResourceIdentifier, GameResources::resource_paths() and the Resources implementation
+#[derive(Resources)]
+#[resources(identifier = "ResourceIdentifier")]
+pub struct GameResources {
+    #[resource(path = "examples/resources/moose.png")]
     _player: Texture2D,
+    #[resource(path = "examples/resources/moose.png")]
     _moose: Texture2D,
+    #[resource(path = "examples/resources/moose_animation.json")]
+    _moose_animation: Vec<u8>,
 }
 
-impl Resources<TextureIdentifier, Texture2D, DefaultFactory> for TextureResources {
-    fn build(
-        builder: &mut ResourceBuilder<TextureIdentifier, Self, Texture2D, DefaultFactory>,
-    ) -> Self {
-        Self {
-            _player: builder.get_or_panic(TextureIdentifier::Player),
-            _moose: builder.get_or_panic(TextureIdentifier::Moose),
-        }
-    }
-}
-
-// BootState will load textures asyncronously whilst drawing the procentage process
+// BootState will load textures and files asyncronously whilst drawing the procentage process
 // when every texture resource is loaded, transition to into_state
 pub struct BootState {
     into_state: Option<Box<dyn State<TransitionData, SharedData>>>,
-    texture_resource_builder:
-        ResourceBuilder<TextureIdentifier, TextureResources, Texture2D, DefaultFactory>,
+    resource_builder:
+        ResourceBuilder<ResourceIdentifier, GameResources, AnyResource, DefaultFactory>,
 }
 
 impl BootState {
     pub fn new(into_state: Box<dyn State<TransitionData, SharedData>>) -> Self {
         Self {
             into_state: Some(into_state),
-            texture_resource_builder: ResourceBuilder::<
-                TextureIdentifier,
-                TextureResources,
-                Texture2D,
+            resource_builder: ResourceBuilder::<
+                ResourceIdentifier,
+                GameResources,
+                AnyResource,
                 DefaultFactory,
-            >::new(
-                [
-                    (TextureIdentifier::Player, "examples/resources/moose.png"),
-                    (TextureIdentifier::Moose, "examples/resources/moose.png"),
-                ]
-                .into(),
-            ),
+            >::new(GameResources::resource_paths())
+            // a missing texture shows up as a checkerboard instead of stopping the boot
+            .with_fallback()
+            .with_concurrency(4)
+            .with_file_sizes(),
         }
     }
 }
@@ -136,13 +124,19 @@ impl State<TransitionData, SharedData> for BootState {
         _delta_time: f32,
         payload: &mut StateManagerPayload<SharedData>,
     ) -> Option<StateManagerCommand<TransitionData, SharedData>> {
-        // load all textures
-        let is_done_loading = self.texture_resource_builder.load_next().await;
+        // load all resources
+        let is_done_loading = self
+            .resource_builder
+            .load_next()
+            .await
+            .unwrap_or_else(|err| panic!("{}", err));
         if !is_done_loading {
             return None;
         }
-        payload.shared_data.texture_resources_optional =
-            Some(self.texture_resource_builder.build());
+        for err in self.resource_builder.fallback_errors() {
+            eprintln!("{}, using fallback", err);
+        }
+        payload.shared_data.resources_optional = Some(self.resource_builder.build());
         // unwrap should be safe
         let into_state = self.into_state.take().unwrap();
         return Some(StateManagerCommand::ChangeStateEx(
@@ -153,10 +147,15 @@ impl State<TransitionData, SharedData> for BootState {
     }
     fn on_draw(&mut self, _shared_data: StateManagerPayload<SharedData>) {
         clear_background(BLACK);
+        let time_left = self
+            .resource_builder
+            .estimated_time_remaining()
+            .unwrap_or(0f32);
         draw_text(
             format!(
-                "BOOTING UP... {:.0}%",
-                self.texture_resource_builder.progress() * 100f32
+                "BOOTING UP... {:.0}% {:.1}s",
+                self.resource_builder.progress() * 100f32,
+                time_left
             )
             .as_str(),
             GAME_SIZE.x * 0.5f32 - 140f32,
@@ -201,7 +200,7 @@ impl State<TransitionData, SharedData> for LoadingState {
 }
 
 pub struct SharedData {
-    texture_resources_optional: Option<TextureResources>,
+    resources_optional: Option<GameResources>,
 }
 
 #[derive(Eq, PartialEq, Hash, Clone, Copy)]
@@ -248,7 +247,7 @@ async fn main() {
         .await
         .unwrap();
     let shared_data = SharedData {
-        texture_resources_optional: None,
+        resources_optional: None,
     };
 
     let mut transition_texture_map = HashMap::new();
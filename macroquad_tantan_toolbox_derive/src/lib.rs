@@ -0,0 +1,228 @@
+use proc_macro::TokenStream;
+use quote::quote;
+use std::collections::HashMap;
+use syn::ext::IdentExt;
+use syn::{parse_macro_input, Data, DeriveInput, Fields, Ident, LitStr};
+
+// #[derive(Resources)]
+// #[resources(identifier = "TextureIdentifier")]
+// pub struct TextureResources {
+//     #[resource(path = "examples/resources/moose.png")]
+//     moose: Texture2D,
+// }
+//
+// generates the identifier enum (one variant per field, TextureResourcesIdentifier when not named),
+// TextureResources::resource_paths() for ResourceBuilder::new and the Resources implementation
+// #[path] would read nicer but rustc keeps that name for modules
+#[proc_macro_derive(Resources, attributes(resources, resource))]
+pub fn derive_resources(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand(input)
+        .unwrap_or_else(|err| err.to_compile_error())
+        .into()
+}
+
+fn expand(input: DeriveInput) -> syn::Result<proc_macro2::TokenStream> {
+    if !input.generics.params.is_empty() {
+        return Err(syn::Error::new_spanned(
+            &input.generics,
+            "#[derive(Resources)] doesn't support generics",
+        ));
+    }
+    let fields = match &input.data {
+        Data::Struct(data) => match &data.fields {
+            Fields::Named(fields) => &fields.named,
+            _ => {
+                return Err(syn::Error::new_spanned(
+                    &input.ident,
+                    "#[derive(Resources)] needs named fields",
+                ))
+            }
+        },
+        _ => {
+            return Err(syn::Error::new_spanned(
+                &input.ident,
+                "#[derive(Resources)] only works on structs",
+            ))
+        }
+    };
+
+    let mut identifier = Ident::new(&format!("{}Identifier", input.ident), input.ident.span());
+    for attr in input
+        .attrs
+        .iter()
+        .filter(|attr| attr.path().is_ident("resources"))
+    {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("identifier") {
+                let name: LitStr = meta.value()?.parse()?;
+                identifier = name.parse()?;
+                Ok(())
+            } else {
+                Err(meta.error("expected identifier = \"...\""))
+            }
+        })?;
+    }
+
+    let mut variants = Vec::new();
+    let mut paths = Vec::new();
+    let mut field_names = Vec::new();
+    let mut field_types = Vec::new();
+    // variant name -> field it came from, two fields can't share a variant
+    let mut variant_fields: HashMap<String, Ident> = HashMap::new();
+    for field in fields.iter() {
+        let field_name = field.ident.clone().expect("NAMED FIELD");
+        let mut path = None;
+        for attr in field
+            .attrs
+            .iter()
+            .filter(|attr| attr.path().is_ident("resource"))
+        {
+            attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("path") {
+                    path = Some(meta.value()?.parse::<LitStr>()?);
+                    Ok(())
+                } else {
+                    Err(meta.error("expected path = \"...\""))
+                }
+            })?;
+        }
+        let path = path.ok_or_else(|| {
+            syn::Error::new_spanned(&field_name, "missing #[resource(path = \"...\")]")
+        })?;
+        // r#type becomes Type
+        let variant_name = upper_camel_case(&field_name.unraw().to_string());
+        let variant = syn::parse_str::<Ident>(&variant_name).map_err(|_| {
+            syn::Error::new_spanned(
+                &field_name,
+                format!(
+                    "`{}` doesn't make a valid variant name, rename the field",
+                    field_name
+                ),
+            )
+        })?;
+        if let Some(other) = variant_fields.insert(variant_name.clone(), field_name.clone()) {
+            return Err(syn::Error::new_spanned(
+                &field_name,
+                format!(
+                    "`{}` and `{}` both become the variant {}, rename one of them",
+                    other, field_name, variant_name
+                ),
+            ));
+        }
+        variants.push(variant);
+        paths.push(path);
+        field_names.push(field_name);
+        field_types.push(field.ty.clone());
+    }
+
+    let name = &input.ident;
+    let vis = &input.vis;
+    let resources = quote!(::macroquad_tantan_toolbox::resources);
+    Ok(quote! {
+        #[derive(Hash, Eq, PartialEq, Clone, Copy, Debug)]
+        #vis enum #identifier {
+            #(#variants,)*
+        }
+
+        impl #name {
+            // every resource the struct needs, pass it to ResourceBuilder::new
+            #vis fn resource_paths() -> Vec<(#identifier, &'static str)> {
+                vec![#((#identifier::#variants, #paths),)*]
+            }
+        }
+
+        impl<ResourceType, F> #resources::Resources<#identifier, ResourceType, F> for #name
+        where
+            F: #resources::ResourceFactory<ResourceType>,
+            #(#field_types: #resources::TakeResource<ResourceType>,)*
+        {
+            fn build(
+                builder: &mut #resources::ResourceBuilder<#identifier, Self, ResourceType, F>,
+            ) -> Self {
+                Self {
+                    #(#field_names: #resources::TakeResource::take_resource(
+                        builder,
+                        #identifier::#variants,
+                    )
+                    .unwrap_or_else(|err| panic!("{}", err)),)*
+                }
+            }
+        }
+    })
+}
+
+// moose_sprite -> MooseSprite, leading underscores of unused fields are dropped
+fn upper_camel_case(name: &str) -> String {
+    name.split('_')
+        .filter(|word| !word.is_empty())
+        .map(|word| {
+            let mut chars = word.chars();
+            match chars.next() {
+                Some(first) => first.to_uppercase().chain(chars).collect(),
+                None => String::new(),
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use syn::parse_quote;
+
+    #[test]
+    fn field_names_to_variants() {
+        assert_eq!(upper_camel_case("moose"), "Moose");
+        assert_eq!(upper_camel_case("moose_sprite"), "MooseSprite");
+        assert_eq!(upper_camel_case("_unused"), "Unused");
+        assert_eq!(upper_camel_case("level__2"), "Level2");
+        assert_eq!(upper_camel_case("älg"), "Älg");
+        assert_eq!(upper_camel_case("__"), "");
+    }
+
+    fn error(input: DeriveInput) -> String {
+        expand(input).unwrap_err().to_string()
+    }
+
+    #[test]
+    fn fields_sharing_a_variant() {
+        let message = error(parse_quote! {
+            struct Sprites {
+                #[resource(path = "a.png")]
+                a_b: Texture2D,
+                #[resource(path = "b.png")]
+                ab: Texture2D,
+                #[resource(path = "c.png")]
+                _a_b: Texture2D,
+            }
+        });
+        assert_eq!(
+            message,
+            "`a_b` and `_a_b` both become the variant AB, rename one of them"
+        );
+    }
+
+    #[test]
+    fn raw_identifiers() {
+        let input: DeriveInput = parse_quote! {
+            struct Sprites {
+                #[resource(path = "type.png")]
+                r#type: Texture2D,
+            }
+        };
+        let output = expand(input).unwrap().to_string();
+        assert!(output.contains("Type ,"), "{}", output);
+
+        let message = error(parse_quote! {
+            struct Sprites {
+                #[resource(path = "a.png")]
+                __: Texture2D,
+            }
+        });
+        assert_eq!(
+            message,
+            "`__` doesn't make a valid variant name, rename the field"
+        );
+    }
+}
@@ -1,11 +1,202 @@
 use macroquad::prelude::*;
-use std::collections::HashMap;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+use std::sync::Arc;
 
+pub mod aseprite;
+pub mod batch;
+pub mod controller;
+pub mod definition;
+pub mod player;
+
+pub use player::{AnimationPlayer, QueuedAnimation};
+
+#[derive(Clone)]
 pub struct AnimationSpriteData {
     pub columns: f32,
     pub rows: f32,
     pub true_size: Vec2,
     pub texture: Texture2D,
+    // explicit frame list, used instead of the columns/rows grid when not empty
+    pub frames: Vec<SpriteFrame>,
+}
+
+// one frame of a packed atlas, the source rect may be trimmed of its transparent border
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SpriteFrame {
+    pub source: Rect,
+    // size of the frame before it was trimmed
+    pub source_size: Vec2,
+    // where the trimmed source sits inside the untrimmed frame
+    pub offset: Vec2,
+    // point inside the untrimmed frame that gets placed at the draw position, in pixels
+    pub pivot: Vec2,
+}
+
+impl SpriteFrame {
+    // untrimmed frame pivoting around its center
+    pub fn from_rect(source: Rect) -> Self {
+        Self {
+            source,
+            source_size: vec2(source.w, source.h),
+            offset: vec2(0f32, 0f32),
+            pivot: vec2(source.w * 0.5f32, source.h * 0.5f32),
+        }
+    }
+
+    // where the trimmed source ends up on screen so the untrimmed frame's pivot lands on pos
+    pub fn dest_rect(&self, pos: Vec2, scale: Vec2, flip_x: bool, flip_y: bool) -> Rect {
+        let (pivot_x, offset_x) = if flip_x {
+            (
+                self.source_size.x - self.pivot.x,
+                self.source_size.x - self.offset.x - self.source.w,
+            )
+        } else {
+            (self.pivot.x, self.offset.x)
+        };
+        let (pivot_y, offset_y) = if flip_y {
+            (
+                self.source_size.y - self.pivot.y,
+                self.source_size.y - self.offset.y - self.source.h,
+            )
+        } else {
+            (self.pivot.y, self.offset.y)
+        };
+        Rect {
+            x: pos.x + (offset_x - pivot_x) * scale.x,
+            y: pos.y + (offset_y - pivot_y) * scale.y,
+            w: self.source.w * scale.x,
+            h: self.source.h * scale.y,
+        }
+    }
+}
+
+impl AnimationSpriteData {
+    pub fn frame(&self, frame: i32) -> SpriteFrame {
+        if let Some(sprite_frame) = self.frames.get(frame as usize) {
+            return *sprite_frame;
+        }
+        let x_index = frame % self.columns as i32;
+        let y_index = (frame as f32 / self.columns).floor();
+        SpriteFrame::from_rect(Rect {
+            x: x_index as f32 * self.true_size.x,
+            y: y_index * self.true_size.y,
+            w: self.true_size.x,
+            h: self.true_size.y,
+        })
+    }
+
+    pub fn frame_rect(&self, frame: i32) -> Rect {
+        self.frame(frame).source
+    }
+
+    pub fn frame_count(&self) -> i32 {
+        if self.frames.is_empty() {
+            (self.columns * self.rows) as i32
+        } else {
+            self.frames.len() as i32
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum AnimationError {
+    // no animation was added with the identifier
+    UnknownAnimation,
+    // end_frame comes before start_frame
+    EmptyFrameRange {
+        start_frame: i32,
+        end_frame: i32,
+    },
+    // the frame lies outside of the sprite sheet
+    FrameOutOfBounds {
+        frame: i32,
+        frame_count: i32,
+    },
+    // the AnimationSet is shared with other instances and can't be changed through this one
+    SharedAnimationSet,
+    // a loop_frame or event frame that is not part of the animation
+    FrameOutsideAnimation {
+        frame: i32,
+        start_frame: i32,
+        end_frame: i32,
+    },
+    // fps has to be above 0 and finite, or update never gets through a frame
+    InvalidFps(f32),
+    // frame_durations needs one duration per frame of the animation
+    FrameDurationCount {
+        expected: usize,
+        found: usize,
+    },
+}
+
+impl std::fmt::Display for AnimationError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            AnimationError::UnknownAnimation => write!(f, "no animation with that identifier"),
+            AnimationError::EmptyFrameRange {
+                start_frame,
+                end_frame,
+            } => write!(
+                f,
+                "end frame {} comes before start frame {}",
+                end_frame, start_frame
+            ),
+            AnimationError::FrameOutOfBounds { frame, frame_count } => write!(
+                f,
+                "frame {} is out of bounds, there are {} frames",
+                frame, frame_count
+            ),
+            AnimationError::SharedAnimationSet => {
+                write!(f, "the animation set is shared with other instances")
+            }
+            AnimationError::FrameOutsideAnimation {
+                frame,
+                start_frame,
+                end_frame,
+            } => write!(
+                f,
+                "frame {} is outside of the animation frames {}..={}",
+                frame, start_frame, end_frame
+            ),
+            AnimationError::InvalidFps(fps) => write!(f, "fps {} has to be above 0", fps),
+            AnimationError::FrameDurationCount { expected, found } => write!(
+                f,
+                "{} frame durations for an animation of {} frames",
+                found, expected
+            ),
+        }
+    }
+}
+
+impl std::error::Error for AnimationError {}
+
+pub struct DrawParam {
+    // radians
+    pub rotation: f32,
+    // rotate around this point, when None the frame rotates around the draw position
+    pub pivot: Option<Vec2>,
+    pub color: Color,
+    pub flip_x: bool,
+    pub flip_y: bool,
+    // size of the untrimmed frame on screen, overrides the instance scale
+    pub dest_size: Option<Vec2>,
+    // draw order inside a SpriteBatch using SpriteOrder::Z
+    pub z: f32,
+}
+
+impl Default for DrawParam {
+    fn default() -> Self {
+        DrawParam {
+            rotation: 0f32,
+            pivot: None,
+            color: WHITE,
+            flip_x: false,
+            flip_y: false,
+            dest_size: None,
+            z: 0f32,
+        }
+    }
 }
 
 pub struct AnimationData {
@@ -13,52 +204,203 @@ pub struct AnimationData {
     pub end_frame: i32,
     pub loop_frame: Option<i32>,
     pub fps: f32,
+    // milliseconds per frame starting at start_frame, overrides fps when set
+    pub frame_durations: Option<Vec<f32>>,
+    // named events fired when the frame is shown: (frame, name)
+    pub events: Vec<(i32, String)>,
+    pub mode: PlaybackMode,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PlaybackMode {
+    // wraps around to loop_frame, or start_frame when there is none
+    #[default]
+    Loop,
+    // plays once and holds the last frame
+    Once,
+    // plays forward then backward, over and over
+    PingPong,
+    // plays from end_frame to start_frame, over and over
+    Reverse,
+    // loops the given amount of times, then holds the last frame
+    LoopTimes(u32),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum AnimationEvent {
+    // a frame with an event attached was shown
+    Frame { frame: i32, name: String },
+    // the animation wrapped back to its loop_frame or start_frame
+    Looped,
+    // the animation reached its end and handed over to the queued or interrupted animation,
+    // or stopped on its last frame
+    Finished,
 }
 
 impl AnimationData {
+    pub fn new(start_frame: i32, end_frame: i32, loop_frame: Option<i32>, fps: f32) -> Self {
+        Self {
+            start_frame,
+            end_frame,
+            loop_frame,
+            fps,
+            frame_durations: None,
+            events: Vec::new(),
+            mode: PlaybackMode::Loop,
+        }
+    }
+
+    pub fn from_frame_durations(
+        start_frame: i32,
+        end_frame: i32,
+        loop_frame: Option<i32>,
+        frame_durations: Vec<f32>,
+    ) -> Self {
+        let total_ms: f32 = frame_durations.iter().sum();
+        Self {
+            start_frame,
+            end_frame,
+            loop_frame,
+            fps: frame_durations.len() as f32 * 1000f32 / total_ms,
+            frame_durations: Some(frame_durations),
+            events: Vec::new(),
+            mode: PlaybackMode::Loop,
+        }
+    }
+
+    pub fn with_event(mut self, frame: i32, name: &str) -> Self {
+        self.events.push((frame, name.to_string()));
+        self
+    }
+
+    pub fn with_mode(mut self, mode: PlaybackMode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    // the frame playback begins on
+    pub fn first_frame(&self) -> i32 {
+        match self.mode {
+            PlaybackMode::Reverse => self.end_frame,
+            _ => self.start_frame,
+        }
+    }
+
+    pub fn validate(&self, frame_count: i32) -> Result<(), AnimationError> {
+        if self.end_frame < self.start_frame {
+            return Err(AnimationError::EmptyFrameRange {
+                start_frame: self.start_frame,
+                end_frame: self.end_frame,
+            });
+        }
+        for frame in [self.start_frame, self.end_frame] {
+            if frame < 0 || frame >= frame_count {
+                return Err(AnimationError::FrameOutOfBounds { frame, frame_count });
+            }
+        }
+        let event_frames = self.events.iter().map(|(frame, _)| *frame);
+        for frame in self.loop_frame.into_iter().chain(event_frames) {
+            if frame < self.start_frame || frame > self.end_frame {
+                return Err(AnimationError::FrameOutsideAnimation {
+                    frame,
+                    start_frame: self.start_frame,
+                    end_frame: self.end_frame,
+                });
+            }
+        }
+        match &self.frame_durations {
+            Some(frame_durations) => {
+                let expected = (self.end_frame - self.start_frame + 1) as usize;
+                if frame_durations.len() != expected {
+                    return Err(AnimationError::FrameDurationCount {
+                        expected,
+                        found: frame_durations.len(),
+                    });
+                }
+            }
+            // also catches NaN
+            None if !(self.fps > 0f32 && self.fps.is_finite()) => {
+                return Err(AnimationError::InvalidFps(self.fps))
+            }
+            None => {}
+        }
+        Ok(())
+    }
+
+    // seconds the frame stays on screen
+    pub fn frame_duration(&self, frame: i32) -> f32 {
+        let duration_ms = self
+            .frame_durations
+            .as_ref()
+            .and_then(|durations| durations.get((frame - self.start_frame) as usize));
+        match duration_ms {
+            // anything below a millisecond would stall update stepping through frames
+            Some(duration_ms) => duration_ms.max(1f32) / 1000f32,
+            None => 1f32 / self.fps,
+        }
+    }
+
+    // seconds to play the clip from start_frame to end_frame once
     pub fn total_time(&self) -> f32 {
-        let start = if let Some(loop_frame) = self.loop_frame {
-            loop_frame
-        } else {
-            self.start_frame
-        };
-        (self.end_frame - start) as f32
+        (self.start_frame..=self.end_frame)
+            .map(|frame| self.frame_duration(frame))
+            .sum()
     }
 }
 
-pub struct AnimationInstance<A>
+// sprite sheet and animations, shared behind an Arc by every instance playing them
+pub struct AnimationSet<A>
 where
     A: Sized,
 {
-    pub timer: f32,
     pub sprite_data: AnimationSpriteData,
     pub animations: HashMap<A, AnimationData>,
-    pub current_animation: A,
-    // auto player this animation after current finishes
-    pub into_animation_optional: Option<A>,
-    pub scale: Vec2,
 }
 
-impl<A> AnimationInstance<A>
+impl<A> AnimationSet<A>
 where
     A: std::cmp::Eq + std::hash::Hash,
 {
-    pub fn new(columns: f32, rows: f32, texture: Texture2D, start_animation: A) -> Self {
+    pub fn new(columns: f32, rows: f32, texture: Texture2D) -> Self {
         let true_size = vec2(texture.width() / columns, texture.height() / rows);
+        Self::from_sprite_data(AnimationSpriteData {
+            columns,
+            rows,
+            true_size,
+            texture,
+            frames: Vec::new(),
+        })
+    }
+
+    // for sheets where every frame has its own source rect
+    pub fn from_frames(frames: Vec<Rect>, texture: Texture2D) -> Self {
+        let sprite_frames = frames.into_iter().map(SpriteFrame::from_rect).collect();
+        Self::from_sprite_frames(sprite_frames, texture)
+    }
+
+    // for packed atlases with trimmed frames and per frame pivots
+    pub fn from_sprite_frames(frames: Vec<SpriteFrame>, texture: Texture2D) -> Self {
+        let true_size = frames
+            .first()
+            .map(|sprite_frame| sprite_frame.source_size)
+            .unwrap_or_else(|| vec2(texture.width(), texture.height()));
+        Self::from_sprite_data(AnimationSpriteData {
+            columns: frames.len() as f32,
+            rows: 1f32,
+            true_size,
+            texture,
+            frames,
+        })
+    }
+
+    pub fn from_sprite_data(sprite_data: AnimationSpriteData) -> Self {
         Self {
-            timer: 0f32,
-            sprite_data: AnimationSpriteData {
-                columns,
-                rows,
-                true_size,
-                texture,
-            },
+            sprite_data,
             animations: HashMap::new(),
-            current_animation: start_animation,
-            into_animation_optional: None,
-            scale: vec2(1., 1.),
         }
     }
+
     pub fn add_animation(
         &mut self,
         start_frame: i32,
@@ -67,84 +409,596 @@ where
         fps: f32,
         identifier: A,
     ) {
-        self.animations.insert(
+        self.try_add_animation(start_frame, end_frame, loop_frame, fps, identifier)
+            .expect("INVALID ANIMATION");
+    }
+
+    pub fn try_add_animation(
+        &mut self,
+        start_frame: i32,
+        end_frame: i32,
+        loop_frame: Option<i32>,
+        fps: f32,
+        identifier: A,
+    ) -> Result<(), AnimationError> {
+        self.try_insert_animation(
+            identifier,
+            AnimationData::new(start_frame, end_frame, loop_frame, fps),
+        )
+    }
+
+    // frame_durations: milliseconds per frame, one entry for each frame from start_frame to end_frame
+    pub fn add_animation_with_durations(
+        &mut self,
+        start_frame: i32,
+        end_frame: i32,
+        loop_frame: Option<i32>,
+        frame_durations: Vec<f32>,
+        identifier: A,
+    ) {
+        self.insert_animation(
             identifier,
-            AnimationData {
+            AnimationData::from_frame_durations(
                 start_frame,
                 end_frame,
                 loop_frame,
-                fps,
-            },
+                frame_durations,
+            ),
         );
     }
 
-    pub fn update(&mut self, dt: f32) {
+    pub fn insert_animation(&mut self, identifier: A, animation_data: AnimationData) {
+        self.try_insert_animation(identifier, animation_data)
+            .expect("INVALID ANIMATION");
+    }
+
+    // checks the frames of the animation against the sprite sheet before adding it
+    pub fn try_insert_animation(
+        &mut self,
+        identifier: A,
+        animation_data: AnimationData,
+    ) -> Result<(), AnimationError> {
+        animation_data.validate(self.sprite_data.frame_count())?;
+        self.animations.insert(identifier, animation_data);
+        Ok(())
+    }
+
+    pub fn set_playback_mode(&mut self, identifier: &A, mode: PlaybackMode) {
+        self.try_set_playback_mode(identifier, mode)
+            .expect("NO ANIMATION");
+    }
+
+    pub fn try_set_playback_mode(
+        &mut self,
+        identifier: &A,
+        mode: PlaybackMode,
+    ) -> Result<(), AnimationError> {
         let animation_data = self
             .animations
-            .get(&self.current_animation)
-            .expect("NO ANIMATION");
-        self.timer += dt * animation_data.fps;
+            .get_mut(identifier)
+            .ok_or(AnimationError::UnknownAnimation)?;
+        animation_data.mode = mode;
+        Ok(())
+    }
 
-        let start_frame = if let Some(loop_frame) = animation_data.loop_frame {
-            loop_frame
-        } else {
-            animation_data.start_frame
+    pub fn add_event(&mut self, identifier: &A, frame: i32, name: &str) {
+        self.try_add_event(identifier, frame, name)
+            .expect("INVALID EVENT");
+    }
+
+    pub fn try_add_event(
+        &mut self,
+        identifier: &A,
+        frame: i32,
+        name: &str,
+    ) -> Result<(), AnimationError> {
+        let animation_data = self
+            .animations
+            .get_mut(identifier)
+            .ok_or(AnimationError::UnknownAnimation)?;
+        if frame < animation_data.start_frame || frame > animation_data.end_frame {
+            return Err(AnimationError::FrameOutsideAnimation {
+                frame,
+                start_frame: animation_data.start_frame,
+                end_frame: animation_data.end_frame,
+            });
+        }
+        animation_data.events.push((frame, name.to_string()));
+        Ok(())
+    }
+}
+
+pub struct AnimationInstance<A>
+where
+    A: Sized,
+{
+    pub set: Arc<AnimationSet<A>>,
+    // timer, current animation, queue and the rest of the playback state
+    pub player: AnimationPlayer<A>,
+    pub scale: Vec2,
+}
+
+impl<A> AnimationInstance<A>
+where
+    A: std::cmp::Eq + std::hash::Hash,
+{
+    pub fn new(columns: f32, rows: f32, texture: Texture2D, start_animation: A) -> Self {
+        Self::from_set(
+            Arc::new(AnimationSet::new(columns, rows, texture)),
+            start_animation,
+        )
+    }
+
+    // for sheets where every frame has its own source rect
+    pub fn from_frames(frames: Vec<Rect>, texture: Texture2D, start_animation: A) -> Self {
+        Self::from_set(
+            Arc::new(AnimationSet::from_frames(frames, texture)),
+            start_animation,
+        )
+    }
+
+    // for packed atlases with trimmed frames and per frame pivots
+    pub fn from_sprite_frames(
+        frames: Vec<SpriteFrame>,
+        texture: Texture2D,
+        start_animation: A,
+    ) -> Self {
+        Self::from_set(
+            Arc::new(AnimationSet::from_sprite_frames(frames, texture)),
+            start_animation,
+        )
+    }
+
+    pub fn from_sprite_data(sprite_data: AnimationSpriteData, start_animation: A) -> Self {
+        Self::from_set(
+            Arc::new(AnimationSet::from_sprite_data(sprite_data)),
+            start_animation,
+        )
+    }
+
+    // spawn many instances from one set, each only carries its own playback state
+    pub fn from_set(set: Arc<AnimationSet<A>>, start_animation: A) -> Self {
+        let mut instance = Self {
+            set,
+            player: AnimationPlayer::new(start_animation),
+            scale: vec2(1., 1.),
         };
-        if self.timer > start_frame as f32 + animation_data.total_time() + 1f32 {
-            if let Some(loop_frame) = animation_data.loop_frame {
-                self.timer = loop_frame as f32;
-            } else {
-                self.timer = animation_data.start_frame as f32;
-            }
-            if let Some(into_animation) = self.into_animation_optional.take() {
-                self.play_animation(into_animation);
+        // the start animation might not be added to a fresh set yet
+        if instance
+            .set
+            .animations
+            .contains_key(&instance.player.current_animation)
+        {
+            instance.restart();
+        }
+        instance
+    }
+
+    // the set can only be changed through the instance while no other instance shares it
+    fn set_mut(&mut self) -> Result<&mut AnimationSet<A>, AnimationError> {
+        Arc::get_mut(&mut self.set).ok_or(AnimationError::SharedAnimationSet)
+    }
+
+    pub fn add_animation(
+        &mut self,
+        start_frame: i32,
+        end_frame: i32,
+        loop_frame: Option<i32>,
+        fps: f32,
+        identifier: A,
+    ) {
+        self.try_add_animation(start_frame, end_frame, loop_frame, fps, identifier)
+            .expect("INVALID ANIMATION");
+    }
+
+    pub fn try_add_animation(
+        &mut self,
+        start_frame: i32,
+        end_frame: i32,
+        loop_frame: Option<i32>,
+        fps: f32,
+        identifier: A,
+    ) -> Result<(), AnimationError> {
+        self.try_insert_animation(
+            identifier,
+            AnimationData::new(start_frame, end_frame, loop_frame, fps),
+        )
+    }
+
+    // frame_durations: milliseconds per frame, one entry for each frame from start_frame to end_frame
+    pub fn add_animation_with_durations(
+        &mut self,
+        start_frame: i32,
+        end_frame: i32,
+        loop_frame: Option<i32>,
+        frame_durations: Vec<f32>,
+        identifier: A,
+    ) {
+        self.insert_animation(
+            identifier,
+            AnimationData::from_frame_durations(
+                start_frame,
+                end_frame,
+                loop_frame,
+                frame_durations,
+            ),
+        );
+    }
+
+    pub fn insert_animation(&mut self, identifier: A, animation_data: AnimationData) {
+        self.try_insert_animation(identifier, animation_data)
+            .expect("INVALID ANIMATION");
+    }
+
+    pub fn try_insert_animation(
+        &mut self,
+        identifier: A,
+        animation_data: AnimationData,
+    ) -> Result<(), AnimationError> {
+        let is_playing = identifier == self.player.current_animation;
+        self.set_mut()?
+            .try_insert_animation(identifier, animation_data)?;
+        // the start animation of new() only gets its clip now, play it from its first frame
+        if is_playing {
+            self.try_restart()?;
+        }
+        Ok(())
+    }
+
+    pub fn set_playback_mode(&mut self, identifier: &A, mode: PlaybackMode) {
+        self.try_set_playback_mode(identifier, mode)
+            .expect("NO ANIMATION");
+    }
+
+    pub fn try_set_playback_mode(
+        &mut self,
+        identifier: &A,
+        mode: PlaybackMode,
+    ) -> Result<(), AnimationError> {
+        self.set_mut()?.try_set_playback_mode(identifier, mode)
+    }
+
+    pub fn add_event(&mut self, identifier: &A, frame: i32, name: &str) {
+        self.try_add_event(identifier, frame, name)
+            .expect("INVALID EVENT");
+    }
+
+    pub fn try_add_event(
+        &mut self,
+        identifier: &A,
+        frame: i32,
+        name: &str,
+    ) -> Result<(), AnimationError> {
+        self.set_mut()?.try_add_event(identifier, frame, name)
+    }
+
+    // swaps every clip at once, keeping the playing animation where it is when it still fits
+    // fails without changing anything when a clip is invalid or the playing animation is gone
+    pub fn try_replace_animations(
+        &mut self,
+        animations: HashMap<A, AnimationData>,
+    ) -> Result<(), AnimationError> {
+        let frame_count = self.set.sprite_data.frame_count();
+        for animation_data in animations.values() {
+            animation_data.validate(frame_count)?;
+        }
+        self.player.try_replace_animations(&animations)?;
+        match Arc::get_mut(&mut self.set) {
+            Some(set) => set.animations = animations,
+            // other instances keep playing the old clips
+            None => {
+                self.set = Arc::new(AnimationSet {
+                    sprite_data: self.set.sprite_data.clone(),
+                    animations,
+                })
             }
         }
+        Ok(())
+    }
+
+    pub fn pause(&mut self) {
+        self.player.pause();
+    }
+
+    pub fn resume(&mut self) {
+        self.player.resume();
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.player.is_paused()
+    }
+
+    // sprite sheet index of the frame being shown
+    pub fn current_frame(&self) -> i32 {
+        self.player.current_frame()
+    }
+
+    // jumps to a frame of the current animation without firing its events
+    pub fn seek_to_frame(&mut self, frame: i32) -> Result<(), AnimationError> {
+        self.player.seek_to_frame(&self.set.animations, frame)
+    }
+
+    // jumps to a point in time of the current pass, the counterpart of normalized_time
+    pub fn seek_normalized(&mut self, normalized_time: f32) -> Result<(), AnimationError> {
+        self.player
+            .seek_normalized(&self.set.animations, normalized_time)
+    }
+
+    // true once a Once or LoopTimes animation holds its last frame
+    pub fn is_finished(&self) -> bool {
+        self.player.is_finished()
+    }
+
+    // how far playback is through the current pass of the animation, from 0 to 1
+    pub fn normalized_time(&self) -> Result<f32, AnimationError> {
+        self.player.normalized_time(&self.set.animations)
+    }
+
+    // passes played of the current animation, 1.5 is halfway through the second pass
+    pub fn played_time(&self) -> Result<f32, AnimationError> {
+        self.player.played_time(&self.set.animations)
+    }
+
+    // events crossed by the last update and by play calls made after it, drain them every frame
+    pub fn drain_events(&mut self) -> std::vec::Drain<'_, AnimationEvent> {
+        self.player.drain_events()
+    }
+
+    pub fn update(&mut self, dt: f32) {
+        self.try_update(dt).expect("NO ANIMATION");
+    }
+
+    pub fn try_update(&mut self, dt: f32) -> Result<(), AnimationError> {
+        self.player.try_update(&self.set.animations, dt)
+    }
+
+    // plays the current animation from its first frame again
+    pub fn restart(&mut self) {
+        self.try_restart().expect("NO ANIMATION");
+    }
+
+    pub fn try_restart(&mut self) -> Result<(), AnimationError> {
+        self.player.try_restart(&self.set.animations)
     }
 
     pub fn play_animation(&mut self, identifier: A) {
-        self.current_animation = identifier;
-        let animation_data = self
-            .animations
-            .get(&self.current_animation)
-            .expect("NO ANIMATION");
-        self.timer = animation_data.start_frame as f32;
+        self.try_play_animation(identifier).expect("NO ANIMATION");
+    }
+
+    // leaves the current animation playing when the identifier is unknown
+    pub fn try_play_animation(&mut self, identifier: A) -> Result<(), AnimationError> {
+        self.player
+            .try_play_animation(&self.set.animations, identifier)
     }
 
-    // playe animation then the second one right after
+    // playe animation then the second one right after, replacing whatever was queued
     pub fn play_animation_then(&mut self, identifier: A, after: A) {
-        self.current_animation = identifier;
-        self.into_animation_optional = Some(after);
-        let animation_data = self
-            .animations
-            .get(&self.current_animation)
+        self.try_play_animation_then(identifier, after)
             .expect("NO ANIMATION");
-        self.timer = animation_data.start_frame as f32;
+    }
+
+    pub fn try_play_animation_then(
+        &mut self,
+        identifier: A,
+        after: A,
+    ) -> Result<(), AnimationError> {
+        self.player
+            .try_play_animation_then(&self.set.animations, identifier, after)
+    }
+
+    // plays the animation repeat times once everything queued before it is done
+    pub fn enqueue(&mut self, identifier: A, repeat: u32) {
+        self.try_enqueue(identifier, repeat).expect("NO ANIMATION");
+    }
+
+    pub fn try_enqueue(&mut self, identifier: A, repeat: u32) -> Result<(), AnimationError> {
+        self.player
+            .try_enqueue(&self.set.animations, identifier, repeat)
+    }
+
+    pub fn clear_queue(&mut self) {
+        self.player.clear_queue();
+    }
+
+    // plays the animation once right now, then picks the cut off animation and the queue back up
+    // returns false when an interrupt with a higher priority is still playing
+    pub fn interrupt(&mut self, identifier: A, priority: i32) -> bool {
+        self.try_interrupt(identifier, priority)
+            .expect("NO ANIMATION")
+    }
+
+    pub fn try_interrupt(&mut self, identifier: A, priority: i32) -> Result<bool, AnimationError> {
+        self.player
+            .try_interrupt(&self.set.animations, identifier, priority)
+    }
+
+    pub fn is_interrupted(&self) -> bool {
+        self.player.is_interrupted()
+    }
+
+    pub fn is_crossfading(&self) -> bool {
+        self.player.is_crossfading()
     }
 
     pub fn draw(&self, pos: &Vec2, flip_x: bool) {
-        let x_index = self.timer as i32 % (self.sprite_data.columns) as i32;
-        let y_index = (self.timer as f32 / self.sprite_data.columns).floor();
+        self.draw_ex(
+            pos,
+            DrawParam {
+                flip_x,
+                ..Default::default()
+            },
+        );
+    }
 
+    // during a crossfade the outgoing and incoming frames are drawn with complementary alpha
+    pub fn draw_ex(&self, pos: &Vec2, draw_param: DrawParam) {
+        let alpha = match self.player.crossfade_blend() {
+            Some((frame, blend)) => {
+                self.draw_frame(frame, pos, &draw_param, 1f32 - blend);
+                blend
+            }
+            None => 1f32,
+        };
+        self.draw_frame(self.player.current_frame(), pos, &draw_param, alpha);
+    }
+
+    // adds the current frame to the batch instead of drawing it right away
+    pub fn draw_batched(&self, batch: &mut batch::SpriteBatch, pos: &Vec2, draw_param: DrawParam) {
+        let alpha = match self.player.crossfade_blend() {
+            Some((frame, blend)) => {
+                self.batch_frame(batch, frame, pos, &draw_param, 1f32 - blend);
+                blend
+            }
+            None => 1f32,
+        };
+        self.batch_frame(batch, self.player.current_frame(), pos, &draw_param, alpha);
+    }
+
+    fn draw_frame(&self, frame: i32, pos: &Vec2, draw_param: &DrawParam, alpha: f32) {
+        let (sprite_frame, dest) = self.frame_dest(frame, pos, draw_param);
         draw_texture_ex(
-            self.sprite_data.texture,
-            pos.x - self.sprite_data.true_size.x * self.scale.x * 0.5f32,
-            pos.y - self.sprite_data.true_size.y * self.scale.y * 0.5f32,
-            WHITE,
+            self.set.sprite_data.texture,
+            dest.x,
+            dest.y,
+            Color {
+                a: draw_param.color.a * alpha,
+                ..draw_param.color
+            },
             DrawTextureParams {
-                flip_x,
-                dest_size: Some(vec2(
-                    self.sprite_data.true_size.x * self.scale.x,
-                    self.sprite_data.true_size.y * self.scale.y,
-                )),
-                source: Some(Rect {
-                    x: x_index as f32 * self.sprite_data.true_size.x,
-                    y: y_index as f32 * self.sprite_data.true_size.y,
-                    w: self.sprite_data.true_size.x,
-                    h: self.sprite_data.true_size.y,
-                }),
-                ..Default::default()
+                flip_x: draw_param.flip_x,
+                flip_y: draw_param.flip_y,
+                rotation: draw_param.rotation,
+                pivot: Some(draw_param.pivot.unwrap_or(*pos)),
+                dest_size: Some(vec2(dest.w, dest.h)),
+                source: Some(sprite_frame.source),
+            },
+        );
+    }
+
+    fn batch_frame(
+        &self,
+        batch: &mut batch::SpriteBatch,
+        frame: i32,
+        pos: &Vec2,
+        draw_param: &DrawParam,
+        alpha: f32,
+    ) {
+        let (sprite_frame, dest) = self.frame_dest(frame, pos, draw_param);
+        batch.add(batch::BatchedSprite {
+            texture: self.set.sprite_data.texture,
+            source: sprite_frame.source,
+            dest,
+            color: Color {
+                a: draw_param.color.a * alpha,
+                ..draw_param.color
             },
+            flip_x: draw_param.flip_x,
+            flip_y: draw_param.flip_y,
+            rotation: draw_param.rotation,
+            pivot: draw_param.pivot.unwrap_or(*pos),
+            y: pos.y,
+            z: draw_param.z,
+        });
+    }
+
+    fn frame_dest(&self, frame: i32, pos: &Vec2, draw_param: &DrawParam) -> (SpriteFrame, Rect) {
+        let sprite_frame = self.set.sprite_data.frame(frame);
+        let scale = match draw_param.dest_size {
+            Some(dest_size) => dest_size / sprite_frame.source_size,
+            None => self.scale,
+        };
+        let dest = sprite_frame.dest_rect(*pos, scale, draw_param.flip_x, draw_param.flip_y);
+        (sprite_frame, dest)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 10x12 pixels trimmed out of a 16x16 frame, pivoting at the bottom left quarter
+    fn trimmed_frame() -> SpriteFrame {
+        SpriteFrame {
+            source: Rect::new(32f32, 0f32, 10f32, 12f32),
+            source_size: vec2(16f32, 16f32),
+            offset: vec2(2f32, 1f32),
+            pivot: vec2(4f32, 12f32),
+        }
+    }
+
+    #[test]
+    fn trimmed_frames_land_on_their_pivot() {
+        let frame = trimmed_frame();
+        let pos = vec2(100f32, 100f32);
+        let one = vec2(1f32, 1f32);
+        assert_eq!(
+            frame.dest_rect(pos, one, false, false),
+            Rect::new(98f32, 89f32, 10f32, 12f32)
+        );
+        // flipping mirrors both the trim offset and the pivot inside the untrimmed frame
+        assert_eq!(
+            frame.dest_rect(pos, one, true, false),
+            Rect::new(92f32, 89f32, 10f32, 12f32)
+        );
+        assert_eq!(
+            frame.dest_rect(pos, one, false, true),
+            Rect::new(98f32, 99f32, 10f32, 12f32)
+        );
+        assert_eq!(
+            frame.dest_rect(pos, vec2(2f32, 3f32), true, true),
+            Rect::new(84f32, 97f32, 20f32, 36f32)
+        );
+    }
+
+    #[test]
+    fn untrimmed_frames_center_on_pos() {
+        let frame = SpriteFrame::from_rect(Rect::new(0f32, 0f32, 16f32, 8f32));
+        assert_eq!(
+            frame.dest_rect(vec2(0f32, 0f32), vec2(1f32, 1f32), true, true),
+            Rect::new(-8f32, -4f32, 16f32, 8f32)
+        );
+    }
+
+    #[test]
+    fn dest_size_overrides_the_instance_scale() {
+        let mut instance =
+            AnimationInstance::from_sprite_frames(vec![trimmed_frame()], Texture2D::empty(), 0);
+        instance.scale = vec2(2f32, 2f32);
+        let pos = vec2(0f32, 0f32);
+        let (_, dest) = instance.frame_dest(0, &pos, &DrawParam::default());
+        assert_eq!(dest, Rect::new(-4f32, -22f32, 20f32, 24f32));
+        let draw_param = DrawParam {
+            dest_size: Some(vec2(32f32, 48f32)),
+            flip_x: true,
+            ..DrawParam::default()
+        };
+        let (sprite_frame, dest) = instance.frame_dest(0, &pos, &draw_param);
+        assert_eq!(sprite_frame, trimmed_frame());
+        assert_eq!(dest, Rect::new(-16f32, -33f32, 20f32, 36f32));
+    }
+
+    #[test]
+    fn start_animation_added_later_starts_at_its_first_frame() {
+        let mut instance = AnimationInstance::new(10f32, 1f32, Texture2D::empty(), "sleep");
+        instance.add_animation(0, 3, None, 13f32, "run");
+        instance.add_animation(4, 9, Some(7), 13f32, "sleep");
+        assert_eq!(instance.current_frame(), 4);
+        for frame in 5..=9 {
+            instance.update(1f32 / 13f32 + 0.001f32);
+            assert_eq!(instance.current_frame(), frame);
+        }
+        assert!(instance.drain_events().next().is_none());
+    }
+
+    #[test]
+    fn once_start_animation_added_later_plays() {
+        let mut instance = AnimationInstance::new(10f32, 1f32, Texture2D::empty(), "wave");
+        instance.insert_animation(
+            "wave",
+            AnimationData::new(4, 6, None, 4f32).with_mode(PlaybackMode::Once),
         );
+        instance.update(0.25f32);
+        assert_eq!(instance.current_frame(), 5);
+        assert!(!instance.is_finished());
     }
 }
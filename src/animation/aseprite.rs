@@ -0,0 +1,465 @@
+use crate::animation::*;
+use crate::resources::{load_decoded_texture, relative_path, FactoryError};
+use serde::de::{Deserializer, MapAccess, SeqAccess, Visitor};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::Arc;
+
+// clip name used when the sheet was exported without any frame tags
+pub const DEFAULT_TAG: &str = "default";
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct AsepriteRect {
+    pub x: f32,
+    pub y: f32,
+    pub w: f32,
+    pub h: f32,
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct AsepriteSize {
+    pub w: f32,
+    pub h: f32,
+}
+
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct AsepriteFrame {
+    // only present in the array export, the hash export stores it as the key
+    #[serde(default)]
+    pub filename: String,
+    pub frame: AsepriteRect,
+    // rotated frames would need rotated source rects, sheets with them fail to parse
+    #[serde(default, deserialize_with = "deny_rotated")]
+    pub rotated: bool,
+    #[serde(default)]
+    pub trimmed: bool,
+    pub sprite_source_size: AsepriteRect,
+    pub source_size: AsepriteSize,
+    // milliseconds
+    pub duration: f32,
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum AsepriteDirection {
+    #[default]
+    Forward,
+    Reverse,
+    Pingpong,
+    PingpongReverse,
+}
+
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+pub struct AsepriteTag {
+    pub name: String,
+    pub from: i32,
+    pub to: i32,
+    #[serde(default)]
+    pub direction: AsepriteDirection,
+    // newer aseprite versions export the repeat count as a string, missing means forever
+    #[serde(default)]
+    pub repeat: Option<String>,
+}
+
+impl AsepriteTag {
+    // pingpong_reverse has no direct equivalent and plays like pingpong
+    pub fn playback_mode(&self) -> PlaybackMode {
+        let repeat = self
+            .repeat
+            .as_ref()
+            .and_then(|repeat| repeat.parse::<u32>().ok())
+            .filter(|repeat| *repeat > 0);
+        match (self.direction, repeat) {
+            (AsepriteDirection::Forward, Some(repeat)) => PlaybackMode::LoopTimes(repeat),
+            (AsepriteDirection::Forward, None) => PlaybackMode::Loop,
+            (AsepriteDirection::Reverse, _) => PlaybackMode::Reverse,
+            (AsepriteDirection::Pingpong, _) | (AsepriteDirection::PingpongReverse, _) => {
+                PlaybackMode::PingPong
+            }
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct AsepriteMeta {
+    // path to the sheet image, relative to the json file
+    #[serde(default)]
+    pub image: String,
+    pub size: AsepriteSize,
+    #[serde(default)]
+    pub frame_tags: Vec<AsepriteTag>,
+}
+
+// the json aseprite writes with "Export Sprite Sheet", both the hash and array variant
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+pub struct AsepriteSheet {
+    #[serde(deserialize_with = "deserialize_frames")]
+    pub frames: Vec<AsepriteFrame>,
+    pub meta: AsepriteMeta,
+}
+
+#[derive(Debug)]
+pub enum AsepriteError {
+    File(FileError),
+    Json(serde_json::Error),
+    // the sprite sheet image is missing or can't be decoded
+    Texture(FactoryError),
+    Animation(AnimationError),
+}
+
+impl fmt::Display for AsepriteError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            AsepriteError::File(err) => write!(f, "can't load aseprite sheet: {}", err),
+            AsepriteError::Json(err) => write!(f, "can't parse aseprite sheet: {}", err),
+            AsepriteError::Texture(err) => write!(f, "can't load sprite sheet: {}", err),
+            AsepriteError::Animation(err) => write!(f, "invalid aseprite tag: {}", err),
+        }
+    }
+}
+
+impl std::error::Error for AsepriteError {}
+
+impl From<FileError> for AsepriteError {
+    fn from(err: FileError) -> Self {
+        AsepriteError::File(err)
+    }
+}
+
+impl From<serde_json::Error> for AsepriteError {
+    fn from(err: serde_json::Error) -> Self {
+        AsepriteError::Json(err)
+    }
+}
+
+impl From<AnimationError> for AsepriteError {
+    fn from(err: AnimationError) -> Self {
+        AsepriteError::Animation(err)
+    }
+}
+
+impl AsepriteSheet {
+    pub fn from_json(json: &str) -> Result<Self, serde_json::Error> {
+        serde_json::from_str(json)
+    }
+
+    // trimmed frames keep their place inside the untrimmed frame, pivoting around its center
+    pub fn sprite_frames(&self) -> Vec<SpriteFrame> {
+        self.frames
+            .iter()
+            .map(|frame| SpriteFrame {
+                source: Rect {
+                    x: frame.frame.x,
+                    y: frame.frame.y,
+                    w: frame.frame.w,
+                    h: frame.frame.h,
+                },
+                source_size: vec2(frame.source_size.w, frame.source_size.h),
+                offset: vec2(frame.sprite_source_size.x, frame.sprite_source_size.y),
+                pivot: vec2(frame.source_size.w * 0.5f32, frame.source_size.h * 0.5f32),
+            })
+            .collect()
+    }
+
+    pub fn frame_rects(&self) -> Vec<Rect> {
+        self.frames
+            .iter()
+            .map(|frame| Rect {
+                x: frame.frame.x,
+                y: frame.frame.y,
+                w: frame.frame.w,
+                h: frame.frame.h,
+            })
+            .collect()
+    }
+
+    // milliseconds per frame
+    pub fn frame_durations(&self) -> Vec<f32> {
+        self.frames.iter().map(|frame| frame.duration).collect()
+    }
+
+    // every frame tag, or a single DEFAULT_TAG spanning the whole sheet when there are none
+    pub fn tags(&self) -> Vec<AsepriteTag> {
+        if !self.meta.frame_tags.is_empty() {
+            return self.meta.frame_tags.clone();
+        }
+        vec![AsepriteTag {
+            name: DEFAULT_TAG.to_string(),
+            from: 0,
+            to: self.frames.len() as i32 - 1,
+            direction: AsepriteDirection::Forward,
+            repeat: None,
+        }]
+    }
+
+    // one clip per tag, keeping the duration of every frame and the tag direction
+    // fails on a tag that points past the frames, or a sheet without any frames
+    pub fn animations(&self) -> Result<HashMap<String, AnimationData>, AnimationError> {
+        let frame_count = self.frames.len() as i32;
+        self.tags()
+            .into_iter()
+            .map(|tag| {
+                if tag.to < tag.from {
+                    return Err(AnimationError::EmptyFrameRange {
+                        start_frame: tag.from,
+                        end_frame: tag.to,
+                    });
+                }
+                for frame in [tag.from, tag.to] {
+                    if frame < 0 || frame >= frame_count {
+                        return Err(AnimationError::FrameOutOfBounds { frame, frame_count });
+                    }
+                }
+                let frame_durations = self.frames[tag.from as usize..=tag.to as usize]
+                    .iter()
+                    .map(|frame| frame.duration)
+                    .collect();
+                let animation_data =
+                    AnimationData::from_frame_durations(tag.from, tag.to, None, frame_durations)
+                        .with_mode(tag.playback_mode());
+                Ok((tag.name, animation_data))
+            })
+            .collect()
+    }
+
+    // wrap it in an Arc to share it between many instances
+    pub fn animation_set(
+        &self,
+        texture: Texture2D,
+    ) -> Result<AnimationSet<String>, AnimationError> {
+        let mut set = AnimationSet::from_sprite_frames(self.sprite_frames(), texture);
+        for (name, animation_data) in self.animations()? {
+            set.try_insert_animation(name, animation_data)?;
+        }
+        Ok(set)
+    }
+
+    pub fn into_animation_instance(
+        &self,
+        texture: Texture2D,
+    ) -> Result<AnimationInstance<String>, AnimationError> {
+        let start_animation = self.tags().remove(0).name;
+        Ok(AnimationInstance::from_set(
+            Arc::new(self.animation_set(texture)?),
+            start_animation,
+        ))
+    }
+}
+
+// loads the json and the sheet image it points to
+pub async fn load_aseprite_animation(
+    json_path: &str,
+) -> Result<AnimationInstance<String>, AsepriteError> {
+    let json = load_string(json_path).await?;
+    let sheet = AsepriteSheet::from_json(&json)?;
+    let image_path = relative_path(json_path, &sheet.meta.image);
+    let texture = load_decoded_texture(&image_path)
+        .await
+        .map_err(AsepriteError::Texture)?;
+    Ok(sheet.into_animation_instance(texture)?)
+}
+
+fn deny_rotated<'de, D>(deserializer: D) -> Result<bool, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    if bool::deserialize(deserializer)? {
+        return Err(serde::de::Error::custom(
+            "rotated frames aren't supported, export the sheet without rotation",
+        ));
+    }
+    Ok(false)
+}
+
+fn deserialize_frames<'de, D>(deserializer: D) -> Result<Vec<AsepriteFrame>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    struct FramesVisitor;
+
+    impl<'de> Visitor<'de> for FramesVisitor {
+        type Value = Vec<AsepriteFrame>;
+
+        fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+            formatter.write_str("an array or a map of aseprite frames")
+        }
+
+        fn visit_seq<S>(self, mut seq: S) -> Result<Self::Value, S::Error>
+        where
+            S: SeqAccess<'de>,
+        {
+            let mut frames = Vec::new();
+            while let Some(frame) = seq.next_element()? {
+                frames.push(frame);
+            }
+            Ok(frames)
+        }
+
+        // keeps the key order, which is the frame order aseprite exported
+        fn visit_map<M>(self, mut map: M) -> Result<Self::Value, M::Error>
+        where
+            M: MapAccess<'de>,
+        {
+            let mut frames = Vec::new();
+            while let Some((filename, mut frame)) = map.next_entry::<String, AsepriteFrame>()? {
+                frame.filename = filename;
+                frames.push(frame);
+            }
+            Ok(frames)
+        }
+    }
+
+    deserializer.deserialize_any(FramesVisitor)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn frame_json(x: f32, duration: f32) -> String {
+        format!(
+            r#"{{ "frame": {{ "x": {x}, "y": 0, "w": 14, "h": 12 }}, "rotated": false, "trimmed": true,
+                "spriteSourceSize": {{ "x": 1, "y": 2, "w": 14, "h": 12 }},
+                "sourceSize": {{ "w": 16, "h": 16 }}, "duration": {duration} }}"#,
+            x = x,
+            duration = duration
+        )
+    }
+
+    fn meta_json(frame_tags: &str) -> String {
+        format!(
+            r#"{{ "image": "moose.png", "size": {{ "w": 42, "h": 12 }}, "frameTags": [{}] }}"#,
+            frame_tags
+        )
+    }
+
+    fn array_sheet(frame_tags: &str) -> AsepriteSheet {
+        let json = format!(
+            r#"{{ "frames": [{}, {}, {}], "meta": {} }}"#,
+            frame_json(0., 100.),
+            frame_json(14., 50.),
+            frame_json(28., 200.),
+            meta_json(frame_tags)
+        );
+        AsepriteSheet::from_json(&json).unwrap()
+    }
+
+    #[test]
+    fn hash_and_array_exports_match() {
+        let json = format!(
+            r#"{{ "frames": {{ "moose 0.aseprite": {}, "moose 1.aseprite": {}, "moose 2.aseprite": {} }}, "meta": {} }}"#,
+            frame_json(0., 100.),
+            frame_json(14., 50.),
+            frame_json(28., 200.),
+            meta_json("")
+        );
+        let hash = AsepriteSheet::from_json(&json).unwrap();
+        let array = array_sheet("");
+        assert_eq!(hash.frame_rects(), array.frame_rects());
+        assert_eq!(hash.frame_durations(), array.frame_durations());
+        assert_eq!(hash.frames[1].filename, "moose 1.aseprite");
+        assert_eq!(hash.meta.image, "moose.png");
+    }
+
+    #[test]
+    fn rotated_frames_error() {
+        let json = format!(
+            r#"{{ "frames": [{}], "meta": {} }}"#,
+            frame_json(0., 100.).replace(r#""rotated": false"#, r#""rotated": true"#),
+            meta_json("")
+        );
+        let err = AsepriteSheet::from_json(&json).unwrap_err();
+        assert!(err.to_string().contains("rotated"), "{}", err);
+    }
+
+    #[test]
+    fn trimmed_frames_keep_their_offset() {
+        let frames = array_sheet("").sprite_frames();
+        assert_eq!(frames.len(), 3);
+        assert_eq!(frames[1].source, Rect::new(14., 0., 14., 12.));
+        assert_eq!(frames[1].source_size, vec2(16., 16.));
+        assert_eq!(frames[1].offset, vec2(1., 2.));
+        assert_eq!(frames[1].pivot, vec2(8., 8.));
+    }
+
+    #[test]
+    fn frame_durations_per_tag() {
+        let sheet =
+            array_sheet(r#"{ "name": "walk", "from": 1, "to": 2, "direction": "forward" }"#);
+        assert_eq!(sheet.frame_durations(), vec![100., 50., 200.]);
+        let animations = sheet.animations().unwrap();
+        let walk = &animations["walk"];
+        assert_eq!((walk.start_frame, walk.end_frame), (1, 2));
+        assert_eq!(walk.frame_durations, Some(vec![50., 200.]));
+    }
+
+    #[test]
+    fn tags_map_to_playback_modes() {
+        let tag = |direction: AsepriteDirection, repeat: Option<&str>| AsepriteTag {
+            name: "tag".to_string(),
+            from: 0,
+            to: 0,
+            direction,
+            repeat: repeat.map(|repeat| repeat.to_string()),
+        };
+        assert_eq!(
+            tag(AsepriteDirection::Forward, None).playback_mode(),
+            PlaybackMode::Loop
+        );
+        assert_eq!(
+            tag(AsepriteDirection::Forward, Some("0")).playback_mode(),
+            PlaybackMode::Loop
+        );
+        assert_eq!(
+            tag(AsepriteDirection::Forward, Some("3")).playback_mode(),
+            PlaybackMode::LoopTimes(3)
+        );
+        assert_eq!(
+            tag(AsepriteDirection::Reverse, None).playback_mode(),
+            PlaybackMode::Reverse
+        );
+        assert_eq!(
+            tag(AsepriteDirection::Pingpong, None).playback_mode(),
+            PlaybackMode::PingPong
+        );
+        assert_eq!(
+            tag(AsepriteDirection::PingpongReverse, None).playback_mode(),
+            PlaybackMode::PingPong
+        );
+    }
+
+    #[test]
+    fn untagged_sheet_gets_the_default_tag() {
+        let sheet = array_sheet("");
+        let tags = sheet.tags();
+        assert_eq!(tags.len(), 1);
+        assert_eq!(tags[0].name, DEFAULT_TAG);
+        assert_eq!((tags[0].from, tags[0].to), (0, 2));
+        let animations = sheet.animations().unwrap();
+        assert_eq!(animations[DEFAULT_TAG].mode, PlaybackMode::Loop);
+    }
+
+    #[test]
+    fn tags_outside_of_the_frames_error() {
+        let sheet = array_sheet(r#"{ "name": "walk", "from": 1, "to": 3 }"#);
+        assert!(matches!(
+            sheet.animations(),
+            Err(AnimationError::FrameOutOfBounds {
+                frame: 3,
+                frame_count: 3
+            })
+        ));
+        let sheet = array_sheet(r#"{ "name": "walk", "from": 2, "to": 1 }"#);
+        assert!(matches!(
+            sheet.animations(),
+            Err(AnimationError::EmptyFrameRange { .. })
+        ));
+        let json = format!(r#"{{ "frames": [], "meta": {} }}"#, meta_json(""));
+        let empty = AsepriteSheet::from_json(&json).unwrap();
+        assert!(empty.animations().is_err());
+        assert!(empty.animation_set(Texture2D::empty()).is_err());
+    }
+}
@@ -0,0 +1,166 @@
+use crate::animation::*;
+use macroquad::models::{draw_mesh, Mesh, Vertex};
+
+// macroquad drops geometry above 5000 indices in a single draw call, 6 indices per quad
+const MAX_QUADS_PER_MESH: usize = 800;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SpriteOrder {
+    // draw in the order the sprites were added
+    Submission,
+    // sprites further down the screen are drawn on top
+    YSort,
+    // lower z is drawn first, see DrawParam::z
+    Z,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BatchedSprite {
+    pub texture: Texture2D,
+    pub source: Rect,
+    pub dest: Rect,
+    pub color: Color,
+    pub flip_x: bool,
+    pub flip_y: bool,
+    // radians, rotating around pivot
+    pub rotation: f32,
+    pub pivot: Vec2,
+    // position used by SpriteOrder::YSort, usually the feet of the sprite
+    pub y: f32,
+    pub z: f32,
+}
+
+// collects sprites over a frame and draws every run of sprites sharing a texture as one mesh
+// instances sharing one sprite sheet end up in a single draw call
+//
+// the order comes first, sprites of different sheets only group when their keys are equal, so
+// sheets interleaved by YSort or Z cost a draw call per texture switch, use one batch per sheet
+// when the order between the sheets doesn't matter
+pub struct SpriteBatch {
+    pub order: SpriteOrder,
+    sprites: Vec<BatchedSprite>,
+    mesh: Mesh,
+}
+
+impl SpriteBatch {
+    pub fn new(order: SpriteOrder) -> Self {
+        Self {
+            order,
+            sprites: Vec::new(),
+            mesh: Mesh {
+                vertices: Vec::new(),
+                indices: Vec::new(),
+                texture: None,
+            },
+        }
+    }
+
+    pub fn add(&mut self, sprite: BatchedSprite) {
+        self.sprites.push(sprite);
+    }
+
+    pub fn len(&self) -> usize {
+        self.sprites.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.sprites.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.sprites.clear();
+    }
+
+    // draws everything added since the last draw and empties the batch
+    pub fn draw(&mut self) {
+        // equal keys group by texture, stable so a texture keeps its submission order
+        let key: Option<fn(&BatchedSprite) -> f32> = match self.order {
+            SpriteOrder::Submission => None,
+            SpriteOrder::YSort => Some(|sprite: &BatchedSprite| sprite.y),
+            SpriteOrder::Z => Some(|sprite: &BatchedSprite| sprite.z),
+        };
+        if let Some(key) = key {
+            self.sprites.sort_by(|a, b| {
+                key(a)
+                    .partial_cmp(&key(b))
+                    .unwrap_or(std::cmp::Ordering::Equal)
+                    .then_with(|| texture_id(a).cmp(&texture_id(b)))
+            });
+        }
+
+        let mut quads = 0;
+        for sprite in self.sprites.iter() {
+            if self.mesh.texture != Some(sprite.texture) || quads == MAX_QUADS_PER_MESH {
+                Self::flush(&mut self.mesh);
+                self.mesh.texture = Some(sprite.texture);
+                quads = 0;
+            }
+            Self::push_quad(&mut self.mesh, sprite);
+            quads += 1;
+        }
+        Self::flush(&mut self.mesh);
+        self.mesh.texture = None;
+        self.sprites.clear();
+    }
+
+    fn flush(mesh: &mut Mesh) {
+        if !mesh.indices.is_empty() {
+            draw_mesh(mesh);
+        }
+        mesh.vertices.clear();
+        mesh.indices.clear();
+    }
+
+    // same corners and uvs draw_texture_ex would produce
+    fn push_quad(mesh: &mut Mesh, sprite: &BatchedSprite) {
+        let texture_size = vec2(sprite.texture.width(), sprite.texture.height());
+        let mut uv_min = vec2(sprite.source.x, sprite.source.y) / texture_size;
+        let mut uv_max = vec2(
+            sprite.source.x + sprite.source.w,
+            sprite.source.y + sprite.source.h,
+        ) / texture_size;
+        if sprite.flip_x {
+            std::mem::swap(&mut uv_min.x, &mut uv_max.x);
+        }
+        if sprite.flip_y {
+            std::mem::swap(&mut uv_min.y, &mut uv_max.y);
+        }
+
+        let dest = sprite.dest;
+        let (sin, cos) = sprite.rotation.sin_cos();
+        let corners = [
+            (vec2(dest.x, dest.y), vec2(uv_min.x, uv_min.y)),
+            (vec2(dest.x + dest.w, dest.y), vec2(uv_max.x, uv_min.y)),
+            (
+                vec2(dest.x + dest.w, dest.y + dest.h),
+                vec2(uv_max.x, uv_max.y),
+            ),
+            (vec2(dest.x, dest.y + dest.h), vec2(uv_min.x, uv_max.y)),
+        ];
+        let first_index = mesh.vertices.len() as u16;
+        for (corner, uv) in corners.iter() {
+            let p = *corner - sprite.pivot;
+            let p = vec2(p.x * cos - p.y * sin, p.x * sin + p.y * cos) + sprite.pivot;
+            mesh.vertices.push(Vertex {
+                position: vec3(p.x, p.y, 0f32),
+                uv: *uv,
+                color: sprite.color,
+            });
+        }
+        mesh.indices.extend_from_slice(&[
+            first_index,
+            first_index + 1,
+            first_index + 2,
+            first_index,
+            first_index + 2,
+            first_index + 3,
+        ]);
+    }
+}
+
+fn texture_id(sprite: &BatchedSprite) -> u32 {
+    sprite
+        .texture
+        .raw_miniquad_texture_handle()
+        .gl_internal_id()
+}
@@ -0,0 +1,249 @@
+use crate::animation::*;
+use std::hash::Hash;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AnimationParameter {
+    Bool(bool),
+    Float(f32),
+}
+
+// a parameter that was never set counts as false / 0
+#[derive(Debug, Clone, PartialEq)]
+pub enum AnimationCondition {
+    IsTrue(String),
+    IsFalse(String),
+    Greater(String, f32),
+    Less(String, f32),
+}
+
+pub struct AnimationTransition<A> {
+    // None makes this an "any state" transition
+    pub from: Option<A>,
+    pub to: A,
+    // every condition has to hold
+    pub conditions: Vec<AnimationCondition>,
+    // passes of the current animation that have to be played before the transition can happen,
+    // counting loops so 1.0 waits for the end of the first pass and 2.5 for the middle of the third
+    // the time only grows while the animation plays, once reached it stays reached
+    pub exit_time: Option<f32>,
+}
+
+impl<A> AnimationTransition<A> {
+    pub fn when_true(&mut self, parameter: &str) -> &mut Self {
+        self.conditions
+            .push(AnimationCondition::IsTrue(parameter.to_string()));
+        self
+    }
+
+    pub fn when_false(&mut self, parameter: &str) -> &mut Self {
+        self.conditions
+            .push(AnimationCondition::IsFalse(parameter.to_string()));
+        self
+    }
+
+    pub fn when_greater(&mut self, parameter: &str, value: f32) -> &mut Self {
+        self.conditions
+            .push(AnimationCondition::Greater(parameter.to_string(), value));
+        self
+    }
+
+    pub fn when_less(&mut self, parameter: &str, value: f32) -> &mut Self {
+        self.conditions
+            .push(AnimationCondition::Less(parameter.to_string(), value));
+        self
+    }
+
+    pub fn with_exit_time(&mut self, exit_time: f32) -> &mut Self {
+        self.exit_time = Some(exit_time);
+        self
+    }
+}
+
+// state machine on top of an AnimationInstance, every animation identifier is a state
+// gameplay code sets parameters each frame and the transitions pick the animation
+pub struct AnimationController<A>
+where
+    A: Sized,
+{
+    pub instance: AnimationInstance<A>,
+    pub transitions: Vec<AnimationTransition<A>>,
+    parameters: HashMap<String, AnimationParameter>,
+}
+
+impl<A> AnimationController<A>
+where
+    A: Eq + Hash + Clone,
+{
+    pub fn new(instance: AnimationInstance<A>) -> Self {
+        Self {
+            instance,
+            transitions: Vec::new(),
+            parameters: HashMap::new(),
+        }
+    }
+
+    pub fn add_transition(&mut self, from: A, to: A) -> &mut AnimationTransition<A> {
+        self.push_transition(Some(from), to)
+    }
+
+    // can happen from whichever animation is playing, checked before regular transitions
+    pub fn add_any_state_transition(&mut self, to: A) -> &mut AnimationTransition<A> {
+        self.push_transition(None, to)
+    }
+
+    fn push_transition(&mut self, from: Option<A>, to: A) -> &mut AnimationTransition<A> {
+        self.transitions.push(AnimationTransition {
+            from,
+            to,
+            conditions: Vec::new(),
+            exit_time: None,
+        });
+        self.transitions.last_mut().unwrap()
+    }
+
+    pub fn set_bool(&mut self, name: &str, value: bool) {
+        self.parameters
+            .insert(name.to_string(), AnimationParameter::Bool(value));
+    }
+
+    pub fn set_float(&mut self, name: &str, value: f32) {
+        self.parameters
+            .insert(name.to_string(), AnimationParameter::Float(value));
+    }
+
+    pub fn get_bool(&self, name: &str) -> bool {
+        match self.parameters.get(name) {
+            Some(AnimationParameter::Bool(value)) => *value,
+            Some(AnimationParameter::Float(value)) => *value != 0f32,
+            None => false,
+        }
+    }
+
+    pub fn get_float(&self, name: &str) -> f32 {
+        match self.parameters.get(name) {
+            Some(AnimationParameter::Float(value)) => *value,
+            Some(AnimationParameter::Bool(value)) => *value as i32 as f32,
+            None => 0f32,
+        }
+    }
+
+    pub fn current_animation(&self) -> &A {
+        &self.instance.player.current_animation
+    }
+
+    pub fn update(&mut self, dt: f32) {
+        self.try_update(dt).expect("NO ANIMATION");
+    }
+
+    // fails when a transition leads to an animation the instance doesn't have,
+    // the current animation keeps playing then
+    pub fn try_update(&mut self, dt: f32) -> Result<(), AnimationError> {
+        if let Some(to) = self.next_animation()? {
+            self.instance.try_play_animation(to)?;
+        }
+        self.instance.try_update(dt)
+    }
+
+    pub fn draw(&self, pos: &Vec2, flip_x: bool) {
+        self.instance.draw(pos, flip_x);
+    }
+
+    // first transition that is allowed to happen, any state transitions win
+    fn next_animation(&self) -> Result<Option<A>, AnimationError> {
+        let current = &self.instance.player.current_animation;
+        let any_state = self
+            .transitions
+            .iter()
+            .filter(|transition| transition.from.is_none() && transition.to != *current);
+        let from_current = self
+            .transitions
+            .iter()
+            .filter(|transition| transition.from.as_ref() == Some(current));
+        for transition in any_state.chain(from_current) {
+            if self.can_transition(transition)? {
+                return Ok(Some(transition.to.clone()));
+            }
+        }
+        Ok(None)
+    }
+
+    fn can_transition(&self, transition: &AnimationTransition<A>) -> Result<bool, AnimationError> {
+        if let Some(exit_time) = transition.exit_time {
+            if self.instance.played_time()? < exit_time {
+                return Ok(false);
+            }
+        }
+        Ok(transition
+            .conditions
+            .iter()
+            .all(|condition| match condition {
+                AnimationCondition::IsTrue(name) => self.get_bool(name),
+                AnimationCondition::IsFalse(name) => !self.get_bool(name),
+                AnimationCondition::Greater(name, value) => self.get_float(name) > *value,
+                AnimationCondition::Less(name, value) => self.get_float(name) < *value,
+            }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 4 frames at 4 fps, one pass takes a second
+    fn controller() -> AnimationController<&'static str> {
+        let mut instance = AnimationInstance::new(4f32, 1f32, Texture2D::empty(), "idle");
+        instance
+            .try_insert_animation("idle", AnimationData::new(0, 3, None, 4f32))
+            .unwrap();
+        instance
+            .try_insert_animation("wave", AnimationData::new(0, 3, None, 4f32))
+            .unwrap();
+        instance.try_restart().unwrap();
+        AnimationController::new(instance)
+    }
+
+    #[test]
+    fn exit_time_counts_loops() {
+        let mut controller = controller();
+        controller
+            .add_transition("idle", "wave")
+            .with_exit_time(1.5f32);
+        for _ in 0..5 {
+            controller.try_update(0.25f32).unwrap();
+        }
+        // the clip looped back to its first frame, that is 1.25 passes
+        assert_eq!(controller.instance.played_time().unwrap(), 1.25f32);
+        assert_eq!(*controller.current_animation(), "idle");
+        controller.try_update(0.25f32).unwrap();
+        controller.try_update(0.25f32).unwrap();
+        assert_eq!(*controller.current_animation(), "wave");
+    }
+
+    #[test]
+    fn exit_time_of_one_fires_on_a_looping_clip() {
+        let mut controller = controller();
+        controller
+            .add_transition("idle", "wave")
+            .with_exit_time(1f32);
+        for _ in 0..4 {
+            controller.try_update(0.25f32).unwrap();
+            assert_eq!(*controller.current_animation(), "idle");
+        }
+        controller.try_update(0.25f32).unwrap();
+        assert_eq!(*controller.current_animation(), "wave");
+    }
+
+    #[test]
+    fn unknown_target_errors_instead_of_panicking() {
+        let mut controller = controller();
+        controller
+            .add_transition("idle", "jump")
+            .when_true("jumping");
+        controller.set_bool("jumping", true);
+        assert!(matches!(
+            controller.try_update(0.25f32),
+            Err(AnimationError::UnknownAnimation)
+        ));
+        assert_eq!(*controller.current_animation(), "idle");
+    }
+}
@@ -0,0 +1,353 @@
+use crate::animation::*;
+#[cfg(not(target_arch = "wasm32"))]
+use crate::resources::reload::modified_time;
+use crate::resources::{load_decoded_texture, relative_path, FactoryError};
+use std::collections::BTreeMap;
+use std::fmt;
+
+// everything needed to build an AnimationInstance<String>, kept in a json file so clips can be
+// tweaked without recompiling
+//
+// {
+//     "texture": "moose.png",
+//     "columns": 10,
+//     "rows": 1,
+//     "start_animation": "run",
+//     "animations": {
+//         "run": { "start_frame": 0, "end_frame": 3, "fps": 15 },
+//         "sleep": { "start_frame": 4, "end_frame": 9, "loop_frame": 7, "fps": 13 }
+//     }
+// }
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct AnimationDefinition {
+    // path to the sprite sheet, relative to the definition file
+    pub texture: String,
+    // grid layout, ignored when frames is not empty
+    #[serde(default = "one")]
+    pub columns: f32,
+    #[serde(default = "one")]
+    pub rows: f32,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub frames: Vec<FrameDefinition>,
+    // first clip by name when missing
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub start_animation: Option<String>,
+    pub animations: BTreeMap<String, ClipDefinition>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct FrameDefinition {
+    pub x: f32,
+    pub y: f32,
+    pub w: f32,
+    pub h: f32,
+    // in pixels from the top left of the frame, the center when missing
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pivot: Option<[f32; 2]>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ClipDefinition {
+    pub start_frame: i32,
+    pub end_frame: i32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub loop_frame: Option<i32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fps: Option<f32>,
+    // milliseconds per frame, overrides fps
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub frame_durations: Option<Vec<f32>>,
+    #[serde(default)]
+    pub mode: PlaybackMode,
+    // (frame, name)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub events: Vec<(i32, String)>,
+}
+
+fn one() -> f32 {
+    1f32
+}
+
+#[derive(Debug)]
+pub enum DefinitionError {
+    File(FileError),
+    Io(std::io::Error),
+    Json(serde_json::Error),
+    // the sprite sheet image is missing or can't be decoded
+    Texture(FactoryError),
+    Animation(AnimationError),
+    // the clip has neither fps nor frame_durations
+    MissingFps(String),
+    NoAnimations,
+}
+
+impl fmt::Display for DefinitionError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DefinitionError::File(err) => write!(f, "can't load animation definition: {}", err),
+            DefinitionError::Io(err) => write!(f, "can't read animation definition: {}", err),
+            DefinitionError::Json(err) => write!(f, "can't parse animation definition: {}", err),
+            DefinitionError::Texture(err) => write!(f, "can't load sprite sheet: {}", err),
+            DefinitionError::Animation(err) => write!(f, "invalid animation: {}", err),
+            DefinitionError::MissingFps(name) => {
+                write!(f, "animation {} needs fps or frame_durations", name)
+            }
+            DefinitionError::NoAnimations => write!(f, "the definition has no animations"),
+        }
+    }
+}
+
+impl std::error::Error for DefinitionError {}
+
+impl From<FileError> for DefinitionError {
+    fn from(err: FileError) -> Self {
+        DefinitionError::File(err)
+    }
+}
+
+impl From<std::io::Error> for DefinitionError {
+    fn from(err: std::io::Error) -> Self {
+        DefinitionError::Io(err)
+    }
+}
+
+impl From<serde_json::Error> for DefinitionError {
+    fn from(err: serde_json::Error) -> Self {
+        DefinitionError::Json(err)
+    }
+}
+
+impl From<AnimationError> for DefinitionError {
+    fn from(err: AnimationError) -> Self {
+        DefinitionError::Animation(err)
+    }
+}
+
+impl ClipDefinition {
+    pub fn animation_data(&self, name: &str) -> Result<AnimationData, DefinitionError> {
+        let animation_data = match (&self.frame_durations, self.fps) {
+            (Some(frame_durations), _) => AnimationData::from_frame_durations(
+                self.start_frame,
+                self.end_frame,
+                self.loop_frame,
+                frame_durations.clone(),
+            ),
+            (None, Some(fps)) => {
+                AnimationData::new(self.start_frame, self.end_frame, self.loop_frame, fps)
+            }
+            (None, None) => return Err(DefinitionError::MissingFps(name.to_string())),
+        };
+        Ok(AnimationData {
+            events: self.events.clone(),
+            ..animation_data.with_mode(self.mode)
+        })
+    }
+}
+
+impl AnimationDefinition {
+    pub fn from_json(json: &str) -> Result<Self, serde_json::Error> {
+        serde_json::from_str(json)
+    }
+
+    pub fn to_json(&self) -> Result<String, serde_json::Error> {
+        serde_json::to_string_pretty(self)
+    }
+
+    pub fn sprite_data(&self, texture: Texture2D) -> AnimationSpriteData {
+        if self.frames.is_empty() {
+            return AnimationSet::<String>::new(self.columns, self.rows, texture).sprite_data;
+        }
+        let frames = self
+            .frames
+            .iter()
+            .map(|frame| {
+                let mut sprite_frame = SpriteFrame::from_rect(Rect {
+                    x: frame.x,
+                    y: frame.y,
+                    w: frame.w,
+                    h: frame.h,
+                });
+                if let Some([x, y]) = frame.pivot {
+                    sprite_frame.pivot = vec2(x, y);
+                }
+                sprite_frame
+            })
+            .collect();
+        AnimationSet::<String>::from_sprite_frames(frames, texture).sprite_data
+    }
+
+    // unvalidated, AnimationSet::try_insert_animation checks them against the sprite sheet
+    pub fn animations(&self) -> Result<HashMap<String, AnimationData>, DefinitionError> {
+        self.animations
+            .iter()
+            .map(|(name, clip)| Ok((name.clone(), clip.animation_data(name)?)))
+            .collect()
+    }
+
+    pub fn animation_set(
+        &self,
+        texture: Texture2D,
+    ) -> Result<AnimationSet<String>, DefinitionError> {
+        let mut set = AnimationSet::from_sprite_data(self.sprite_data(texture));
+        for (name, animation_data) in self.animations()? {
+            set.try_insert_animation(name, animation_data)?;
+        }
+        Ok(set)
+    }
+
+    pub fn into_animation_instance(
+        &self,
+        texture: Texture2D,
+    ) -> Result<AnimationInstance<String>, DefinitionError> {
+        let start_animation = match &self.start_animation {
+            Some(start_animation) => start_animation.clone(),
+            None => self
+                .animations
+                .keys()
+                .next()
+                .cloned()
+                .ok_or(DefinitionError::NoAnimations)?,
+        };
+        let set = self.animation_set(texture)?;
+        if !set.animations.contains_key(&start_animation) {
+            return Err(AnimationError::UnknownAnimation.into());
+        }
+        Ok(AnimationInstance::from_set(Arc::new(set), start_animation))
+    }
+}
+
+// loads the json and the sprite sheet it points to
+pub async fn load_animation_definition(
+    path: &str,
+) -> Result<AnimationInstance<String>, DefinitionError> {
+    let json = load_string(path).await?;
+    let definition = AnimationDefinition::from_json(&json)?;
+    let texture_path = relative_path(path, &definition.texture);
+    let texture = load_decoded_texture(&texture_path)
+        .await
+        .map_err(DefinitionError::Texture)?;
+    definition.into_animation_instance(texture)
+}
+
+// opt in reloading for development, polls the modified time of the definition file
+// only the clips are swapped, the sprite sheet stays the same
+#[cfg(not(target_arch = "wasm32"))]
+pub struct AnimationReloader {
+    pub path: String,
+    modified: Option<std::time::SystemTime>,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl AnimationReloader {
+    pub fn new(path: &str) -> Self {
+        Self {
+            path: path.to_string(),
+            modified: modified_time(path),
+        }
+    }
+
+    // returns true when the file changed and the clips were swapped
+    // on error the instance keeps playing the clips it had
+    pub fn poll(
+        &mut self,
+        instance: &mut AnimationInstance<String>,
+    ) -> Result<bool, DefinitionError> {
+        let modified = modified_time(&self.path);
+        if modified == self.modified {
+            return Ok(false);
+        }
+        self.modified = modified;
+        let json = std::fs::read_to_string(&self.path)?;
+        let definition = AnimationDefinition::from_json(&json)?;
+        instance.try_replace_animations(definition.animations()?)?;
+        Ok(true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const JSON: &str = r#"{
+        "texture": "moose.png",
+        "columns": 10,
+        "rows": 1,
+        "start_animation": "sleep",
+        "animations": {
+            "run": { "start_frame": 0, "end_frame": 3, "fps": 15, "events": [[1, "step"]] },
+            "sleep": { "start_frame": 4, "end_frame": 6, "loop_frame": 5, "frame_durations": [100, 200, 100], "mode": "ping_pong" }
+        }
+    }"#;
+
+    fn clip(fps: Option<f32>, frame_durations: Option<Vec<f32>>) -> ClipDefinition {
+        ClipDefinition {
+            start_frame: 0,
+            end_frame: 1,
+            loop_frame: None,
+            fps,
+            frame_durations,
+            mode: PlaybackMode::Once,
+            events: vec![(1, "step".to_string())],
+        }
+    }
+
+    #[test]
+    fn clips_use_fps_or_frame_durations() {
+        let animation_data = clip(Some(8f32), None).animation_data("run").unwrap();
+        assert_eq!(animation_data.fps, 8f32);
+        assert_eq!(animation_data.frame_durations, None);
+        assert_eq!(animation_data.mode, PlaybackMode::Once);
+        assert_eq!(animation_data.events, vec![(1, "step".to_string())]);
+
+        // frame_durations win over fps
+        let animation_data = clip(Some(8f32), Some(vec![100f32, 300f32]))
+            .animation_data("run")
+            .unwrap();
+        assert_eq!(animation_data.frame_durations, Some(vec![100f32, 300f32]));
+        assert_eq!(animation_data.frame_duration(1), 0.3f32);
+
+        assert!(matches!(
+            clip(None, None).animation_data("run"),
+            Err(DefinitionError::MissingFps(name)) if name == "run"
+        ));
+    }
+
+    #[test]
+    fn json_round_trip() {
+        let definition = AnimationDefinition::from_json(JSON).unwrap();
+        assert_eq!(definition.animations["sleep"].mode, PlaybackMode::PingPong);
+        assert_eq!(definition.animations["sleep"].loop_frame, Some(5));
+        let json = definition.to_json().unwrap();
+        assert_eq!(AnimationDefinition::from_json(&json).unwrap(), definition);
+    }
+
+    #[test]
+    fn start_animation() {
+        let mut definition = AnimationDefinition::from_json(JSON).unwrap();
+        let instance = definition
+            .into_animation_instance(Texture2D::empty())
+            .unwrap();
+        assert_eq!(instance.player.current_animation, "sleep");
+        assert_eq!(instance.current_frame(), 4);
+
+        // the first clip by name
+        definition.start_animation = None;
+        let instance = definition
+            .into_animation_instance(Texture2D::empty())
+            .unwrap();
+        assert_eq!(instance.player.current_animation, "run");
+
+        definition.start_animation = Some("jump".to_string());
+        assert!(matches!(
+            definition.into_animation_instance(Texture2D::empty()),
+            Err(DefinitionError::Animation(AnimationError::UnknownAnimation))
+        ));
+
+        definition.start_animation = None;
+        definition.animations.clear();
+        assert!(matches!(
+            definition.into_animation_instance(Texture2D::empty()),
+            Err(DefinitionError::NoAnimations)
+        ));
+    }
+}
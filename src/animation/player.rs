@@ -0,0 +1,997 @@
+use crate::animation::*;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct QueuedAnimation<A> {
+    pub identifier: A,
+    // passes through the clip before moving on to the next queued one, at least 1
+    pub repeat: u32,
+}
+
+// frame that was showing when the animation switched, faded out over crossfade_duration
+struct Crossfade {
+    frame: i32,
+    elapsed: f32,
+}
+
+// playback state of the animation an interrupt cut off, restored once the interrupt ends
+struct Interruption<A> {
+    priority: i32,
+    animation: A,
+    timer: f32,
+    direction: i32,
+    loops_played: u32,
+    repeats: u32,
+    finished: bool,
+}
+
+// playback state of one animation without any sprite sheet or texture, so it can run headless
+// every call that needs the clips gets them passed in, usually the animations of an AnimationSet
+pub struct AnimationPlayer<A>
+where
+    A: Sized,
+{
+    // sprite sheet index of the frame being shown, the fraction is how far into that frame
+    // read it through current_frame, move it with seek_to_frame or seek_normalized
+    timer: f32,
+    pub current_animation: A,
+    // played in order, each one after the current animation finished its passes
+    pub queue: VecDeque<QueuedAnimation<A>>,
+    // playback speed multiplier, 0.5 plays at half speed
+    pub speed: f32,
+    // seconds the previous frame fades out while the new animation fades in, 0 switches instantly
+    pub crossfade_duration: f32,
+    paused: bool,
+    // events of the last update and of play calls made after it, see drain_events
+    events: Vec<AnimationEvent>,
+    // how many of events the last update left, the next update drops them when nobody drained
+    stale_events: usize,
+    // 1 when stepping forward through the frames, -1 when stepping backward
+    direction: i32,
+    loops_played: u32,
+    // passes of the current animation before the queue moves on
+    repeats: u32,
+    finished: bool,
+    interruption: Option<Interruption<A>>,
+    crossfade: Option<Crossfade>,
+}
+
+impl<A> AnimationPlayer<A>
+where
+    A: std::cmp::Eq + std::hash::Hash,
+{
+    // stays on frame 0 until try_restart moves it to the first frame of the animation
+    pub fn new(start_animation: A) -> Self {
+        Self {
+            timer: 0f32,
+            current_animation: start_animation,
+            queue: VecDeque::new(),
+            speed: 1f32,
+            crossfade_duration: 0f32,
+            paused: false,
+            events: Vec::new(),
+            stale_events: 0,
+            direction: 1,
+            loops_played: 0,
+            repeats: 1,
+            finished: false,
+            interruption: None,
+            crossfade: None,
+        }
+    }
+
+    pub fn pause(&mut self) {
+        self.paused = true;
+    }
+
+    pub fn resume(&mut self) {
+        self.paused = false;
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    // sprite sheet index of the frame being shown
+    pub fn current_frame(&self) -> i32 {
+        self.timer.floor() as i32
+    }
+
+    // jumps to a frame of the current animation without firing its events
+    pub fn seek_to_frame(
+        &mut self,
+        animations: &HashMap<A, AnimationData>,
+        frame: i32,
+    ) -> Result<(), AnimationError> {
+        let animation_data = animations
+            .get(&self.current_animation)
+            .ok_or(AnimationError::UnknownAnimation)?;
+        if frame < animation_data.start_frame || frame > animation_data.end_frame {
+            return Err(AnimationError::FrameOutsideAnimation {
+                frame,
+                start_frame: animation_data.start_frame,
+                end_frame: animation_data.end_frame,
+            });
+        }
+        self.timer = frame as f32;
+        self.finished = false;
+        Ok(())
+    }
+
+    // jumps to a point in time of the current pass, the counterpart of normalized_time
+    pub fn seek_normalized(
+        &mut self,
+        animations: &HashMap<A, AnimationData>,
+        normalized_time: f32,
+    ) -> Result<(), AnimationError> {
+        let animation_data = animations
+            .get(&self.current_animation)
+            .ok_or(AnimationError::UnknownAnimation)?;
+        let frames: Vec<i32> = if self.direction == 1 {
+            (animation_data.start_frame..=animation_data.end_frame).collect()
+        } else {
+            (animation_data.start_frame..=animation_data.end_frame)
+                .rev()
+                .collect()
+        };
+        let mut time_left = normalized_time.clamp(0f32, 1f32) * animation_data.total_time();
+        // seeking all the way to the end shows the last frame
+        self.timer = frames[frames.len() - 1] as f32;
+        for frame in frames {
+            let frame_duration = animation_data.frame_duration(frame);
+            if time_left < frame_duration {
+                let timer = frame as f32 + time_left / frame_duration;
+                // rounding can push the timer onto the next frame
+                self.timer = if timer.floor() as i32 == frame {
+                    timer
+                } else {
+                    frame as f32
+                };
+                break;
+            }
+            time_left -= frame_duration;
+        }
+        self.finished = false;
+        Ok(())
+    }
+
+    // true once a Once or LoopTimes animation holds its last frame
+    pub fn is_finished(&self) -> bool {
+        self.finished
+    }
+
+    // how far playback is through the current pass of the animation, from 0 to 1
+    pub fn normalized_time(
+        &self,
+        animations: &HashMap<A, AnimationData>,
+    ) -> Result<f32, AnimationError> {
+        let animation_data = animations
+            .get(&self.current_animation)
+            .ok_or(AnimationError::UnknownAnimation)?;
+        if self.finished {
+            return Ok(1f32);
+        }
+        let frame = self.timer.floor() as i32;
+        let played_frames = if self.direction == 1 {
+            animation_data.start_frame..frame
+        } else {
+            frame + 1..animation_data.end_frame + 1
+        };
+        let elapsed = played_frames
+            .map(|played_frame| animation_data.frame_duration(played_frame))
+            .sum::<f32>()
+            + self.timer.fract() * animation_data.frame_duration(frame);
+        Ok((elapsed / animation_data.total_time()).clamp(0f32, 1f32))
+    }
+
+    // passes played of the current animation, 1.5 is halfway through the second pass
+    // unlike normalized_time it keeps growing while the animation loops
+    pub fn played_time(
+        &self,
+        animations: &HashMap<A, AnimationData>,
+    ) -> Result<f32, AnimationError> {
+        let normalized_time = self.normalized_time(animations)?;
+        if self.finished {
+            // the last pass is already counted
+            return Ok(self.loops_played as f32);
+        }
+        Ok(self.loops_played as f32 + normalized_time)
+    }
+
+    // events crossed by the last update and by play calls made after it, in the order they happened
+    // undrained events are dropped by the following update so players nobody listens to don't grow
+    pub fn drain_events(&mut self) -> std::vec::Drain<'_, AnimationEvent> {
+        self.stale_events = 0;
+        self.events.drain(..)
+    }
+
+    pub fn try_update(
+        &mut self,
+        animations: &HashMap<A, AnimationData>,
+        dt: f32,
+    ) -> Result<(), AnimationError> {
+        self.events.drain(..self.stale_events);
+        let result = self.step(animations, dt);
+        self.stale_events = self.events.len();
+        result
+    }
+
+    // step frame by frame so a large dt carries its leftover time into the following frames
+    fn step(
+        &mut self,
+        animations: &HashMap<A, AnimationData>,
+        dt: f32,
+    ) -> Result<(), AnimationError> {
+        if self.paused {
+            return Ok(());
+        }
+        let mut time_left = dt * self.speed.max(0f32);
+        if let Some(crossfade) = self.crossfade.as_mut() {
+            crossfade.elapsed += time_left;
+            if crossfade.elapsed >= self.crossfade_duration {
+                self.crossfade = None;
+            }
+        }
+        while time_left > 0f32 {
+            if self.finished {
+                // a clip queued after the animation already stopped starts right away
+                match self.queue.pop_front() {
+                    Some(queued) => self.try_play_queued(animations, queued)?,
+                    None => break,
+                }
+            }
+            let animation_data = animations
+                .get(&self.current_animation)
+                .ok_or(AnimationError::UnknownAnimation)?;
+            let frame = self.timer.floor();
+            let frame_duration = animation_data.frame_duration(frame as i32);
+            // clips that skipped AnimationData::validate could otherwise spin here forever
+            if !(frame_duration > 0f32 && frame_duration.is_finite()) {
+                return Err(AnimationError::InvalidFps(animation_data.fps));
+            }
+            let time_to_next_frame = (frame + 1f32 - self.timer) * frame_duration;
+            let timer = self.timer + time_left / frame_duration;
+            // rounding can push the timer onto the next frame, that counts as reaching it
+            if time_left < time_to_next_frame && timer < frame + 1f32 {
+                self.timer = timer;
+                return Ok(());
+            }
+            time_left = (time_left - time_to_next_frame).max(0f32);
+
+            let next_frame = frame as i32 + self.direction;
+            if next_frame >= animation_data.start_frame && next_frame <= animation_data.end_frame {
+                self.enter_frame(animations, next_frame);
+                continue;
+            }
+
+            // stepped past either end of the animation
+            let start_frame = animation_data.start_frame;
+            let end_frame = animation_data.end_frame;
+            let loop_frame = animation_data.loop_frame.unwrap_or(start_frame);
+            let mode = animation_data.mode;
+            if mode == PlaybackMode::PingPong && self.direction == 1 {
+                self.direction = -1;
+                self.enter_frame(animations, (end_frame - 1).max(start_frame));
+                continue;
+            }
+            self.loops_played += 1;
+            if let Some(interruption) = self.interruption.take() {
+                self.events.push(AnimationEvent::Finished);
+                self.resume_interrupted(interruption);
+                continue;
+            }
+            // a LoopTimes clip plays all of its loops before the queue moves on
+            let passes = match mode {
+                PlaybackMode::LoopTimes(times) => times.max(self.repeats),
+                _ => self.repeats,
+            };
+            if self.loops_played >= passes {
+                if let Some(queued) = self.queue.pop_front() {
+                    self.events.push(AnimationEvent::Finished);
+                    self.try_play_queued(animations, queued)?;
+                    continue;
+                }
+            }
+            match mode {
+                PlaybackMode::Loop => {
+                    self.events.push(AnimationEvent::Looped);
+                    self.enter_frame(animations, loop_frame);
+                }
+                PlaybackMode::Reverse => {
+                    self.events.push(AnimationEvent::Looped);
+                    self.enter_frame(animations, end_frame);
+                }
+                PlaybackMode::PingPong => {
+                    self.events.push(AnimationEvent::Looped);
+                    self.direction = 1;
+                    self.enter_frame(animations, (start_frame + 1).min(end_frame));
+                }
+                PlaybackMode::LoopTimes(times) if self.loops_played < times => {
+                    self.events.push(AnimationEvent::Looped);
+                    self.enter_frame(animations, loop_frame);
+                }
+                PlaybackMode::Once | PlaybackMode::LoopTimes(_)
+                    if self.loops_played < self.repeats =>
+                {
+                    self.events.push(AnimationEvent::Looped);
+                    self.enter_frame(animations, loop_frame);
+                }
+                PlaybackMode::Once | PlaybackMode::LoopTimes(_) => {
+                    self.events.push(AnimationEvent::Finished);
+                    self.finished = true;
+                    self.timer = frame;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    // moves the timer to the start of the frame and records the events attached to it
+    fn enter_frame(&mut self, animations: &HashMap<A, AnimationData>, frame: i32) {
+        self.timer = frame as f32;
+        if let Some(animation_data) = animations.get(&self.current_animation) {
+            for (event_frame, name) in animation_data.events.iter() {
+                if *event_frame == frame {
+                    self.events.push(AnimationEvent::Frame {
+                        frame,
+                        name: name.clone(),
+                    });
+                }
+            }
+        }
+    }
+
+    // plays the current animation from its first frame again
+    pub fn try_restart(
+        &mut self,
+        animations: &HashMap<A, AnimationData>,
+    ) -> Result<(), AnimationError> {
+        let animation_data = animations
+            .get(&self.current_animation)
+            .ok_or(AnimationError::UnknownAnimation)?;
+        self.direction = match animation_data.mode {
+            PlaybackMode::Reverse => -1,
+            _ => 1,
+        };
+        self.loops_played = 0;
+        self.finished = false;
+        self.enter_frame(animations, animation_data.first_frame());
+        Ok(())
+    }
+
+    // leaves the current animation playing when the identifier is unknown
+    // cancels a running interrupt, the queue keeps going once the new animation finished
+    pub fn try_play_animation(
+        &mut self,
+        animations: &HashMap<A, AnimationData>,
+        identifier: A,
+    ) -> Result<(), AnimationError> {
+        if !animations.contains_key(&identifier) {
+            return Err(AnimationError::UnknownAnimation);
+        }
+        self.interruption = None;
+        self.start_crossfade();
+        self.current_animation = identifier;
+        self.repeats = 1;
+        self.try_restart(animations)
+    }
+
+    fn try_play_queued(
+        &mut self,
+        animations: &HashMap<A, AnimationData>,
+        queued: QueuedAnimation<A>,
+    ) -> Result<(), AnimationError> {
+        self.try_play_animation(animations, queued.identifier)?;
+        self.repeats = queued.repeat.max(1);
+        Ok(())
+    }
+
+    // playe animation then the second one right after, replacing whatever was queued
+    pub fn try_play_animation_then(
+        &mut self,
+        animations: &HashMap<A, AnimationData>,
+        identifier: A,
+        after: A,
+    ) -> Result<(), AnimationError> {
+        if !animations.contains_key(&after) {
+            return Err(AnimationError::UnknownAnimation);
+        }
+        self.try_play_animation(animations, identifier)?;
+        self.queue.clear();
+        self.queue.push_back(QueuedAnimation {
+            identifier: after,
+            repeat: 1,
+        });
+        Ok(())
+    }
+
+    // plays the animation repeat times once everything queued before it is done
+    pub fn try_enqueue(
+        &mut self,
+        animations: &HashMap<A, AnimationData>,
+        identifier: A,
+        repeat: u32,
+    ) -> Result<(), AnimationError> {
+        if !animations.contains_key(&identifier) {
+            return Err(AnimationError::UnknownAnimation);
+        }
+        self.queue.push_back(QueuedAnimation {
+            identifier,
+            repeat: repeat.max(1),
+        });
+        Ok(())
+    }
+
+    // the current animation keeps playing by its own playback mode
+    pub fn clear_queue(&mut self) {
+        self.queue.clear();
+    }
+
+    // plays the animation once right now, then picks the cut off animation and the queue back up
+    // returns false when an interrupt with a higher priority is still playing
+    pub fn try_interrupt(
+        &mut self,
+        animations: &HashMap<A, AnimationData>,
+        identifier: A,
+        priority: i32,
+    ) -> Result<bool, AnimationError> {
+        if !animations.contains_key(&identifier) {
+            return Err(AnimationError::UnknownAnimation);
+        }
+        if let Some(interruption) = &self.interruption {
+            if interruption.priority > priority {
+                return Ok(false);
+            }
+        }
+        self.start_crossfade();
+        let cut_off = std::mem::replace(&mut self.current_animation, identifier);
+        let interruption = match self.interruption.take() {
+            // an interrupt of an interrupt still resumes the animation that was cut off first
+            Some(interruption) => Interruption {
+                priority,
+                ..interruption
+            },
+            None => Interruption {
+                priority,
+                animation: cut_off,
+                timer: self.timer,
+                direction: self.direction,
+                loops_played: self.loops_played,
+                repeats: self.repeats,
+                finished: self.finished,
+            },
+        };
+        self.repeats = 1;
+        self.try_restart(animations)?;
+        self.interruption = Some(interruption);
+        Ok(true)
+    }
+
+    pub fn is_interrupted(&self) -> bool {
+        self.interruption.is_some()
+    }
+
+    fn resume_interrupted(&mut self, interruption: Interruption<A>) {
+        self.start_crossfade();
+        self.current_animation = interruption.animation;
+        self.timer = interruption.timer;
+        self.direction = interruption.direction;
+        self.loops_played = interruption.loops_played;
+        self.repeats = interruption.repeats;
+        self.finished = interruption.finished;
+    }
+
+    // called right before switching animation, while the outgoing frame is still current
+    fn start_crossfade(&mut self) {
+        self.crossfade = if self.crossfade_duration > 0f32 {
+            Some(Crossfade {
+                frame: self.current_frame(),
+                elapsed: 0f32,
+            })
+        } else {
+            None
+        };
+    }
+
+    // outgoing frame and how far the fade is, from 0 to 1
+    // setting crossfade_duration to 0 ends a running fade
+    pub fn crossfade_blend(&self) -> Option<(i32, f32)> {
+        self.crossfade
+            .as_ref()
+            .filter(|_| self.crossfade_duration > 0f32)
+            .map(|crossfade| {
+                (
+                    crossfade.frame,
+                    (crossfade.elapsed / self.crossfade_duration).clamp(0f32, 1f32),
+                )
+            })
+    }
+
+    pub fn is_crossfading(&self) -> bool {
+        self.crossfade_blend().is_some()
+    }
+
+    // keeps playing from the same spot under a new clip table, restarting when the frame is gone
+    // fails without changing anything when the playing animation is missing
+    pub fn try_replace_animations(
+        &mut self,
+        animations: &HashMap<A, AnimationData>,
+    ) -> Result<(), AnimationError> {
+        let animation_data = animations
+            .get(&self.current_animation)
+            .ok_or(AnimationError::UnknownAnimation)?;
+        let frame = self.current_frame();
+        self.queue
+            .retain(|queued| animations.contains_key(&queued.identifier));
+        if let Some(interruption) = &self.interruption {
+            if !animations.contains_key(&interruption.animation) {
+                self.interruption = None;
+            }
+        }
+        if frame < animation_data.start_frame || frame > animation_data.end_frame {
+            self.try_restart(animations)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 4 fps keeps every frame at 0.25 seconds, exact in f32
+    const FRAME: f32 = 0.25f32;
+
+    fn animations(
+        clips: Vec<(&'static str, AnimationData)>,
+    ) -> HashMap<&'static str, AnimationData> {
+        clips.into_iter().collect()
+    }
+
+    fn started(
+        animations: &HashMap<&'static str, AnimationData>,
+        start: &'static str,
+    ) -> AnimationPlayer<&'static str> {
+        let mut player = AnimationPlayer::new(start);
+        player.try_restart(animations).unwrap();
+        player
+    }
+
+    // frame shown and events crossed after each of the steps
+    fn step(
+        player: &mut AnimationPlayer<&'static str>,
+        animations: &HashMap<&'static str, AnimationData>,
+        steps: usize,
+    ) -> (Vec<i32>, Vec<AnimationEvent>) {
+        let mut events = Vec::new();
+        let frames = (0..steps)
+            .map(|_| {
+                player.try_update(animations, FRAME).unwrap();
+                events.extend(player.drain_events());
+                player.current_frame()
+            })
+            .collect();
+        (frames, events)
+    }
+
+    fn step_frames(
+        player: &mut AnimationPlayer<&'static str>,
+        animations: &HashMap<&'static str, AnimationData>,
+        steps: usize,
+    ) -> Vec<i32> {
+        step(player, animations, steps).0
+    }
+
+    fn loop_events(events: &[AnimationEvent]) -> usize {
+        events
+            .iter()
+            .filter(|event| **event == AnimationEvent::Looped)
+            .count()
+    }
+
+    #[test]
+    fn starts_on_start_frame() {
+        let animations = animations(vec![("walk", AnimationData::new(2, 5, None, 4f32))]);
+        let player = started(&animations, "walk");
+        assert_eq!(player.current_frame(), 2);
+    }
+
+    #[test]
+    fn wraps_after_exactly_the_clip_length() {
+        // 4 frames of 0.25 seconds wrap at 1 second, not one frame later
+        let animations = animations(vec![("walk", AnimationData::new(2, 5, None, 4f32))]);
+        let mut player = started(&animations, "walk");
+        player.try_update(&animations, 3f32 * FRAME).unwrap();
+        assert_eq!(player.current_frame(), 5);
+        player.try_update(&animations, FRAME * 0.5f32).unwrap();
+        assert_eq!(player.current_frame(), 5);
+        player.try_update(&animations, FRAME * 0.5f32).unwrap();
+        assert_eq!(player.current_frame(), 2);
+        assert_eq!(loop_events(&player.drain_events().collect::<Vec<_>>()), 1);
+    }
+
+    #[test]
+    fn never_shows_a_frame_past_the_end() {
+        let animations = animations(vec![("walk", AnimationData::new(2, 5, None, 4f32))]);
+        let mut player = started(&animations, "walk");
+        let frames = step_frames(&mut player, &animations, 12);
+        assert!(frames.iter().all(|frame| (2..=5).contains(frame)));
+        assert_eq!(frames, vec![3, 4, 5, 2, 3, 4, 5, 2, 3, 4, 5, 2]);
+    }
+
+    #[test]
+    fn loop_frame_in_the_middle() {
+        let animations = animations(vec![("sleep", AnimationData::new(0, 3, Some(2), 4f32))]);
+        let mut player = started(&animations, "sleep");
+        let (frames, events) = step(&mut player, &animations, 7);
+        assert_eq!(frames, vec![1, 2, 3, 2, 3, 2, 3]);
+        assert_eq!(loop_events(&events), 2);
+    }
+
+    #[test]
+    fn loop_frame_on_the_end_frame_holds_it() {
+        let animations = animations(vec![("sleep", AnimationData::new(0, 3, Some(3), 4f32))]);
+        let mut player = started(&animations, "sleep");
+        let (frames, events) = step(&mut player, &animations, 6);
+        assert_eq!(frames, vec![1, 2, 3, 3, 3, 3]);
+        assert_eq!(loop_events(&events), 3);
+    }
+
+    #[test]
+    fn loop_frame_on_the_start_frame_loops_the_whole_clip() {
+        let animations = animations(vec![
+            ("explicit", AnimationData::new(1, 3, Some(1), 4f32)),
+            ("implicit", AnimationData::new(1, 3, None, 4f32)),
+        ]);
+        let mut explicit = started(&animations, "explicit");
+        let mut implicit = started(&animations, "implicit");
+        assert_eq!(
+            step_frames(&mut explicit, &animations, 7),
+            step_frames(&mut implicit, &animations, 7)
+        );
+    }
+
+    #[test]
+    fn single_frame_clip_loops_every_frame() {
+        let animations = animations(vec![("idle", AnimationData::new(4, 4, None, 4f32))]);
+        let mut player = started(&animations, "idle");
+        let (frames, events) = step(&mut player, &animations, 3);
+        assert_eq!(frames, vec![4, 4, 4]);
+        assert_eq!(loop_events(&events), 3);
+    }
+
+    #[test]
+    fn large_dt_carries_into_the_following_frames() {
+        let animations = animations(vec![("walk", AnimationData::new(0, 3, None, 4f32))]);
+        let mut player = started(&animations, "walk");
+        // nine frames: two full loops and one frame into the third
+        player.try_update(&animations, 9f32 * FRAME).unwrap();
+        assert_eq!(player.current_frame(), 1);
+        assert_eq!(loop_events(&player.drain_events().collect::<Vec<_>>()), 2);
+    }
+
+    #[test]
+    fn small_dt_accumulates() {
+        let animations = animations(vec![("walk", AnimationData::new(0, 3, None, 4f32))]);
+        let mut player = started(&animations, "walk");
+        for _ in 0..3 {
+            player.try_update(&animations, 0.1f32).unwrap();
+        }
+        assert_eq!(player.current_frame(), 1);
+    }
+
+    #[test]
+    fn frame_durations_override_fps() {
+        let animations = animations(vec![(
+            "attack",
+            AnimationData::from_frame_durations(0, 2, None, vec![250f32, 500f32, 250f32]),
+        )]);
+        let mut player = started(&animations, "attack");
+        assert_eq!(
+            step_frames(&mut player, &animations, 5),
+            vec![1, 1, 2, 0, 1]
+        );
+    }
+
+    #[test]
+    fn once_holds_the_last_frame() {
+        let animations = animations(vec![(
+            "die",
+            AnimationData::new(0, 2, None, 4f32).with_mode(PlaybackMode::Once),
+        )]);
+        let mut player = started(&animations, "die");
+        let (frames, events) = step(&mut player, &animations, 5);
+        assert_eq!(frames, vec![1, 2, 2, 2, 2]);
+        assert!(player.is_finished());
+        assert_eq!(player.normalized_time(&animations).unwrap(), 1f32);
+        assert_eq!(events, vec![AnimationEvent::Finished]);
+    }
+
+    #[test]
+    fn loop_times_finishes_after_the_last_pass() {
+        let animations = animations(vec![(
+            "blink",
+            AnimationData::new(0, 1, None, 4f32).with_mode(PlaybackMode::LoopTimes(2)),
+        )]);
+        let mut player = started(&animations, "blink");
+        assert_eq!(
+            step_frames(&mut player, &animations, 5),
+            vec![1, 0, 1, 1, 1]
+        );
+        assert!(player.is_finished());
+    }
+
+    #[test]
+    fn ping_pong_bounces_without_repeating_the_ends() {
+        let animations = animations(vec![(
+            "swing",
+            AnimationData::new(0, 2, None, 4f32).with_mode(PlaybackMode::PingPong),
+        )]);
+        let mut player = started(&animations, "swing");
+        assert_eq!(
+            step_frames(&mut player, &animations, 6),
+            vec![1, 2, 1, 0, 1, 2]
+        );
+    }
+
+    #[test]
+    fn reverse_starts_on_the_end_frame() {
+        let animations = animations(vec![(
+            "rewind",
+            AnimationData::new(0, 2, None, 4f32).with_mode(PlaybackMode::Reverse),
+        )]);
+        let mut player = started(&animations, "rewind");
+        assert_eq!(player.current_frame(), 2);
+        assert_eq!(step_frames(&mut player, &animations, 4), vec![1, 0, 2, 1]);
+    }
+
+    #[test]
+    fn play_animation_then_chains_at_the_end() {
+        let animations = animations(vec![
+            ("attack", AnimationData::new(0, 1, None, 4f32)),
+            ("idle", AnimationData::new(5, 6, None, 4f32)),
+        ]);
+        let mut player = started(&animations, "idle");
+        player
+            .try_play_animation_then(&animations, "attack", "idle")
+            .unwrap();
+        assert_eq!(step_frames(&mut player, &animations, 4), vec![1, 5, 6, 5]);
+        assert_eq!(player.current_animation, "idle");
+    }
+
+    #[test]
+    fn play_animation_then_waits_for_loop_times() {
+        let animations = animations(vec![
+            (
+                "blink",
+                AnimationData::new(0, 1, None, 4f32).with_mode(PlaybackMode::LoopTimes(3)),
+            ),
+            ("idle", AnimationData::new(5, 5, None, 4f32)),
+        ]);
+        let mut player = started(&animations, "idle");
+        player
+            .try_play_animation_then(&animations, "blink", "idle")
+            .unwrap();
+        assert_eq!(
+            step_frames(&mut player, &animations, 7),
+            vec![1, 0, 1, 0, 1, 5, 5]
+        );
+    }
+
+    #[test]
+    fn queue_plays_every_repeat() {
+        let animations = animations(vec![
+            ("idle", AnimationData::new(0, 0, None, 4f32)),
+            ("punch", AnimationData::new(1, 2, None, 4f32)),
+            ("kick", AnimationData::new(3, 3, None, 4f32)),
+        ]);
+        let mut player = started(&animations, "idle");
+        player.try_enqueue(&animations, "punch", 2).unwrap();
+        player.try_enqueue(&animations, "kick", 1).unwrap();
+        assert_eq!(
+            step_frames(&mut player, &animations, 7),
+            vec![1, 2, 1, 2, 3, 3, 3]
+        );
+        assert!(player.queue.is_empty());
+    }
+
+    #[test]
+    fn interrupt_resumes_where_it_cut_off() {
+        let animations = animations(vec![
+            ("combo", AnimationData::new(0, 3, None, 4f32)),
+            ("hurt", AnimationData::new(8, 8, None, 4f32)),
+            ("taunt", AnimationData::new(9, 9, None, 4f32)),
+        ]);
+        let mut player = started(&animations, "combo");
+        player.try_update(&animations, FRAME).unwrap();
+        assert!(player.try_interrupt(&animations, "hurt", 1).unwrap());
+        assert!(!player.try_interrupt(&animations, "taunt", 0).unwrap());
+        assert_eq!(player.current_frame(), 8);
+        assert_eq!(step_frames(&mut player, &animations, 2), vec![1, 2]);
+        assert!(!player.is_interrupted());
+    }
+
+    #[test]
+    fn undrained_events_only_keep_the_last_update() {
+        let animations = animations(vec![(
+            "walk",
+            AnimationData::new(0, 1, None, 4f32).with_event(0, "step"),
+        )]);
+        let mut player = started(&animations, "walk");
+        for _ in 0..100 {
+            player.try_update(&animations, 2f32 * FRAME).unwrap();
+        }
+        assert_eq!(
+            player.drain_events().collect::<Vec<_>>(),
+            vec![
+                AnimationEvent::Looped,
+                AnimationEvent::Frame {
+                    frame: 0,
+                    name: "step".to_string()
+                }
+            ]
+        );
+    }
+
+    #[test]
+    fn events_of_a_play_call_survive_the_next_update() {
+        let animations = animations(vec![
+            ("idle", AnimationData::new(0, 1, None, 4f32)),
+            (
+                "jump",
+                AnimationData::new(2, 3, None, 4f32).with_event(2, "takeoff"),
+            ),
+        ]);
+        let mut player = started(&animations, "idle");
+        player.try_update(&animations, FRAME).unwrap();
+        player.try_play_animation(&animations, "jump").unwrap();
+        player.try_update(&animations, FRAME).unwrap();
+        assert_eq!(
+            player.drain_events().collect::<Vec<_>>(),
+            vec![AnimationEvent::Frame {
+                frame: 2,
+                name: "takeoff".to_string()
+            }]
+        );
+    }
+
+    #[test]
+    fn invalid_fps_errors_instead_of_hanging() {
+        for fps in [-4f32, 0f32, f32::NAN, f32::INFINITY] {
+            let animations = animations(vec![("walk", AnimationData::new(0, 3, None, fps))]);
+            assert!(animations["walk"].validate(4).is_err(), "fps {}", fps);
+            let mut player = started(&animations, "walk");
+            assert!(
+                player.try_update(&animations, 0.016f32).is_err(),
+                "fps {}",
+                fps
+            );
+            assert!(player.drain_events().next().is_none());
+        }
+    }
+
+    #[test]
+    fn frame_durations_need_one_per_frame() {
+        for frame_durations in [vec![], vec![100f32, 100f32]] {
+            let found = frame_durations.len();
+            let animation_data = AnimationData::from_frame_durations(0, 2, None, frame_durations);
+            assert_eq!(
+                animation_data.validate(3),
+                Err(AnimationError::FrameDurationCount { expected: 3, found })
+            );
+        }
+        let animation_data = AnimationData::from_frame_durations(0, 2, None, vec![100f32; 3]);
+        assert_eq!(animation_data.validate(3), Ok(()));
+    }
+
+    #[test]
+    fn events_have_to_be_inside_the_animation() {
+        let animation_data = AnimationData::new(2, 4, None, 4f32).with_event(4, "step");
+        assert_eq!(animation_data.validate(8), Ok(()));
+        let animation_data = animation_data.with_event(5, "step");
+        assert_eq!(
+            animation_data.validate(8),
+            Err(AnimationError::FrameOutsideAnimation {
+                frame: 5,
+                start_frame: 2,
+                end_frame: 4
+            })
+        );
+    }
+
+    #[test]
+    fn tiny_steps_stay_inside_the_clip() {
+        let animations = animations(vec![("walk", AnimationData::new(2, 5, None, 12f32))]);
+        let mut player = started(&animations, "walk");
+        player.speed = 0.0001f32;
+        // one ulp before the end of the last frame, the step is less than the time left in it
+        // but adding it rounds the timer up to 6
+        player.timer = 6f32 - 4f32 * f32::EPSILON;
+        player.try_update(&animations, 0.00025f32).unwrap();
+        assert!((2..=5).contains(&player.current_frame()));
+        let mut seed = 1u32;
+        for _ in 0..100_000 {
+            seed = seed.wrapping_mul(1_664_525).wrapping_add(1_013_904_223);
+            let dt = 0.01f32 + (seed >> 8) as f32 / (1u32 << 24) as f32 * 0.01f32;
+            player.try_update(&animations, dt).unwrap();
+            assert!((2..=5).contains(&player.current_frame()));
+        }
+    }
+
+    #[test]
+    fn crossfade_fades_out_the_previous_frame() {
+        let animations = animations(vec![
+            ("walk", AnimationData::new(0, 3, None, 4f32)),
+            ("idle", AnimationData::new(5, 6, None, 4f32)),
+        ]);
+        let mut player = started(&animations, "walk");
+        player.crossfade_duration = 0.5f32;
+        step(&mut player, &animations, 1);
+        assert_eq!(player.crossfade_blend(), None);
+        player.try_play_animation(&animations, "idle").unwrap();
+        assert_eq!(player.crossfade_blend(), Some((1, 0f32)));
+        step(&mut player, &animations, 1);
+        assert_eq!(player.crossfade_blend(), Some((1, 0.5f32)));
+        step(&mut player, &animations, 1);
+        assert_eq!(player.crossfade_blend(), None);
+        assert!(!player.is_crossfading());
+    }
+
+    #[test]
+    fn crossfade_duration_set_to_zero_ends_the_fade() {
+        let animations = animations(vec![
+            ("walk", AnimationData::new(0, 3, None, 4f32)),
+            ("idle", AnimationData::new(5, 6, None, 4f32)),
+        ]);
+        let mut player = started(&animations, "walk");
+        player.crossfade_duration = 0.5f32;
+        player.try_play_animation(&animations, "idle").unwrap();
+        step(&mut player, &animations, 1);
+        player.crossfade_duration = 0f32;
+        assert_eq!(player.crossfade_blend(), None);
+        assert!(!player.is_crossfading());
+        step(&mut player, &animations, 1);
+        assert_eq!(player.crossfade_blend(), None);
+
+        // no fade starts without a duration
+        player.try_play_animation(&animations, "walk").unwrap();
+        assert!(!player.is_crossfading());
+    }
+
+    #[test]
+    fn paused_and_speed() {
+        let animations = animations(vec![("walk", AnimationData::new(0, 3, None, 4f32))]);
+        let mut player = started(&animations, "walk");
+        player.pause();
+        player.try_update(&animations, 1f32).unwrap();
+        assert_eq!(player.current_frame(), 0);
+        player.resume();
+        player.speed = 2f32;
+        player.try_update(&animations, FRAME).unwrap();
+        assert_eq!(player.current_frame(), 2);
+    }
+
+    #[test]
+    fn unknown_animation_keeps_the_current_one() {
+        let animations = animations(vec![("walk", AnimationData::new(0, 3, None, 4f32))]);
+        let mut player = started(&animations, "walk");
+        assert_eq!(
+            player.try_play_animation(&animations, "fly"),
+            Err(AnimationError::UnknownAnimation)
+        );
+        assert_eq!(player.current_animation, "walk");
+    }
+
+    #[test]
+    fn replace_animations_keeps_the_frame() {
+        let old = animations(vec![("walk", AnimationData::new(0, 3, None, 4f32))]);
+        let new = animations(vec![("walk", AnimationData::new(0, 5, None, 8f32))]);
+        let mut player = started(&old, "walk");
+        player.try_update(&old, 2f32 * FRAME).unwrap();
+        player.try_replace_animations(&new).unwrap();
+        assert_eq!(player.current_frame(), 2);
+        assert_eq!(
+            player.try_replace_animations(&animations(vec![])),
+            Err(AnimationError::UnknownAnimation)
+        );
+    }
+}
@@ -0,0 +1,29 @@
+// packs a directory into a single file PackFactory can read
+// cargo run --bin pack_builder -- examples/resources assets.pack
+use macroquad_tantan_toolbox::resources::pack::{pack_directory, Pack};
+use std::path::Path;
+
+fn main() {
+    let args: Vec<String> = std::env::args().collect();
+    if args.len() != 3 {
+        eprintln!("usage: pack_builder <directory> <output>");
+        std::process::exit(1);
+    }
+    let pack = pack_directory(Path::new(&args[1])).unwrap_or_else(|err| {
+        eprintln!("{}", err);
+        std::process::exit(1);
+    });
+    // read it back before writing so a broken pack never ends up on disk
+    let file_count = match Pack::from_bytes(pack.clone()) {
+        Ok(written) => written.paths().count(),
+        Err(err) => {
+            eprintln!("pack_builder wrote an invalid pack: {}", err);
+            std::process::exit(1);
+        }
+    };
+    std::fs::write(&args[2], &pack).unwrap_or_else(|err| {
+        eprintln!("can't write {}: {}", args[2], err);
+        std::process::exit(1);
+    });
+    println!("packed {} files into {}", file_count, args[2]);
+}
@@ -1,37 +1,283 @@
+use async_trait::async_trait;
 use macroquad::audio::*;
 use macroquad::prelude::*;
-use std::collections::HashMap;
+use std::collections::{HashMap, VecDeque};
+use std::convert::TryFrom;
 use std::fmt::Debug;
+use std::future::Future;
 use std::hash::Hash;
 use std::marker::PhantomData;
+use std::pin::Pin;
+use std::task::Poll;
 
-// THIS doesn't work on wasm builds atm due to futures::executor::block_on not being allowed in wasm
+pub mod bytes;
+pub mod cache;
+pub mod embedded;
+pub mod pack;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod reload;
+
+pub use bytes::BytesSource;
+pub use cache::{ResourceCache, ResourceHandle, Unload};
+pub use embedded::EmbeddedFactory;
+pub use pack::{load_pack, PackFactory};
+#[cfg(not(target_arch = "wasm32"))]
+pub use reload::ResourceReloader;
+
+// #[derive(Resources)], see macroquad_tantan_toolbox_derive
+pub use macroquad_tantan_toolbox_derive::Resources;
+
+// loads through macroquad's own async file loading, so it works the same on native and wasm
 
 pub struct DefaultFactory;
+#[async_trait]
 impl ResourceFactory<Texture2D> for DefaultFactory {
-    fn load_resource(path: &str) -> Texture2D {
-        let texture = futures::executor::block_on(load_texture(path)).unwrap();
+    async fn load_resource(path: &str) -> Result<Texture2D, FactoryError> {
+        load_decoded_texture(path).await
+    }
+
+    async fn fallback(_path: &str) -> Option<Texture2D> {
+        let texture = Texture2D::from_image(&checkerboard_image());
         texture.set_filter(FilterMode::Nearest);
-        texture
+        Some(texture)
     }
 }
 
+#[async_trait]
 impl ResourceFactory<Image> for DefaultFactory {
-    fn load_resource(path: &str) -> Image {
-        let image = futures::executor::block_on(load_image(path)).unwrap();
-        image
+    async fn load_resource(path: &str) -> Result<Image, FactoryError> {
+        load_decoded_image(path).await
+    }
+
+    async fn fallback(_path: &str) -> Option<Image> {
+        Some(checkerboard_image())
     }
 }
 
+#[async_trait]
 impl ResourceFactory<Sound> for DefaultFactory {
-    fn load_resource(path: &str) -> Sound {
-        let file = futures::executor::block_on(load_sound(path));
-        file.unwrap()
+    async fn load_resource(path: &str) -> Result<Sound, FactoryError> {
+        Ok(load_sound(path).await?)
+    }
+
+    async fn fallback(_path: &str) -> Option<Sound> {
+        load_sound_from_bytes(&silent_wav()).await.ok()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ResourceKind {
+    Texture,
+    Image,
+    Sound,
+    Font,
+    // shader source code
+    Shader,
+    Bytes,
+}
+
+impl ResourceKind {
+    // image files load as Image, ResourceBuilder::try_get_texture uploads them when asked for a texture
+    // only formats decode_image can read count as images, jpg, bmp and gif load as Bytes
+    pub fn from_path(path: &str) -> Self {
+        let extension = match path.rsplit_once('.') {
+            Some((_, extension)) => extension.to_lowercase(),
+            None => return ResourceKind::Bytes,
+        };
+        match extension.as_str() {
+            "png" | "tga" => ResourceKind::Image,
+            "wav" | "ogg" => ResourceKind::Sound,
+            "ttf" | "otf" => ResourceKind::Font,
+            "glsl" | "vert" | "frag" | "vs" | "fs" => ResourceKind::Shader,
+            _ => ResourceKind::Bytes,
+        }
+    }
+}
+
+// lets one ResourceBuilder load every kind of file, read them back with the typed getters
+// ResourceBuilder::try_get_texture, try_get_image, try_get_sound...
+pub enum AnyResource {
+    Texture(Texture2D),
+    Image(Image),
+    Sound(Sound),
+    Font(Font),
+    Shader(String),
+    Bytes(Vec<u8>),
+}
+
+impl AnyResource {
+    pub fn kind(&self) -> ResourceKind {
+        match self {
+            AnyResource::Texture(_) => ResourceKind::Texture,
+            AnyResource::Image(_) => ResourceKind::Image,
+            AnyResource::Sound(_) => ResourceKind::Sound,
+            AnyResource::Font(_) => ResourceKind::Font,
+            AnyResource::Shader(_) => ResourceKind::Shader,
+            AnyResource::Bytes(_) => ResourceKind::Bytes,
+        }
     }
 }
 
+// picks what to load from the file extension, see ResourceKind::from_path
+#[async_trait]
+impl ResourceFactory<AnyResource> for DefaultFactory {
+    async fn load_resource(path: &str) -> Result<AnyResource, FactoryError> {
+        Ok(match ResourceKind::from_path(path) {
+            // from_path never picks Texture
+            ResourceKind::Texture | ResourceKind::Image => {
+                AnyResource::Image(load_decoded_image(path).await?)
+            }
+            ResourceKind::Sound => AnyResource::Sound(load_sound(path).await?),
+            ResourceKind::Font => AnyResource::Font(load_ttf_font(path).await?),
+            ResourceKind::Shader => AnyResource::Shader(load_string(path).await?),
+            ResourceKind::Bytes => AnyResource::Bytes(load_file(path).await?),
+        })
+    }
+
+    // fonts, shaders and bytes have no sensible placeholder
+    async fn fallback(path: &str) -> Option<AnyResource> {
+        match ResourceKind::from_path(path) {
+            ResourceKind::Texture | ResourceKind::Image => {
+                Some(AnyResource::Image(checkerboard_image()))
+            }
+            ResourceKind::Sound => <Self as ResourceFactory<Sound>>::fallback(path)
+                .await
+                .map(AnyResource::Sound),
+            _ => None,
+        }
+    }
+}
+
+pub type FactoryError = Box<dyn std::error::Error + Send + Sync>;
+
+type LoadingResource<ResourceType> =
+    Pin<Box<dyn Future<Output = Result<ResourceType, FactoryError>> + Send>>;
+
+// never block inside load_resource, wasm has no way to wait on a future other than awaiting it
+#[async_trait]
 pub trait ResourceFactory<ResourceType> {
-    fn load_resource(path: &str) -> ResourceType;
+    async fn load_resource(path: &str) -> Result<ResourceType, FactoryError>;
+
+    // placeholder used instead of a resource that failed to load, see ResourceBuilder::with_fallback
+    async fn fallback(_path: &str) -> Option<ResourceType> {
+        None
+    }
+}
+
+// macroquad panics on bytes it can't decode, half written files and broken packs shouldn't
+// take the game down, so images are decoded here instead, png and tga like macroquad
+pub(crate) fn decode_image(bytes: &[u8]) -> Result<Image, FactoryError> {
+    let image = ::image::load_from_memory(bytes)?.to_rgba8();
+    let width = u16::try_from(image.width())?;
+    let height = u16::try_from(image.height())?;
+    Ok(Image {
+        bytes: image.into_raw(),
+        width,
+        height,
+    })
+}
+
+pub(crate) async fn load_decoded_image(path: &str) -> Result<Image, FactoryError> {
+    decode_image(&load_file(path).await?)
+}
+
+pub(crate) async fn load_decoded_texture(path: &str) -> Result<Texture2D, FactoryError> {
+    let texture = Texture2D::from_image(&load_decoded_image(path).await?);
+    texture.set_filter(FilterMode::Nearest);
+    Ok(texture)
+}
+
+// path of a file referenced from another one, relative to the directory of the referencing file
+pub(crate) fn relative_path(from_file: &str, path: &str) -> String {
+    match from_file.rfind('/') {
+        Some(index) => format!("{}/{}", &from_file[..index], path),
+        None => path.to_string(),
+    }
+}
+
+// magenta and black 16x16 checkerboard, hard to miss in game
+pub fn checkerboard_image() -> Image {
+    let mut image = Image::gen_image_color(16, 16, MAGENTA);
+    for y in 0..16 {
+        for x in 0..16 {
+            if (x / 8 + y / 8) % 2 == 1 {
+                image.set_pixel(x, y, BLACK);
+            }
+        }
+    }
+    image
+}
+
+// a tenth of a second of 16 bit mono silence
+fn silent_wav() -> Vec<u8> {
+    let sample_rate = 44100u32;
+    let data_size = sample_rate / 10 * 2;
+    let mut wav = Vec::with_capacity(44 + data_size as usize);
+    wav.extend_from_slice(b"RIFF");
+    wav.extend_from_slice(&(36 + data_size).to_le_bytes());
+    wav.extend_from_slice(b"WAVEfmt ");
+    wav.extend_from_slice(&16u32.to_le_bytes());
+    // pcm, 1 channel
+    wav.extend_from_slice(&1u16.to_le_bytes());
+    wav.extend_from_slice(&1u16.to_le_bytes());
+    wav.extend_from_slice(&sample_rate.to_le_bytes());
+    wav.extend_from_slice(&(sample_rate * 2).to_le_bytes());
+    // block align, bits per sample
+    wav.extend_from_slice(&2u16.to_le_bytes());
+    wav.extend_from_slice(&16u16.to_le_bytes());
+    wav.extend_from_slice(b"data");
+    wav.extend_from_slice(&data_size.to_le_bytes());
+    wav.resize(44 + data_size as usize, 0);
+    wav
+}
+
+#[derive(Debug)]
+pub enum ResourceErrorKind {
+    // the factory couldn't load the file
+    Load(FactoryError),
+    // never queued, or already taken out of the builder
+    Missing,
+    // asked a typed getter for a resource of another kind, the resource stays in the builder
+    WrongKind {
+        expected: ResourceKind,
+        found: ResourceKind,
+    },
+}
+
+#[derive(Debug)]
+pub struct ResourceError {
+    // debug print of the resource identifier
+    pub identifier: String,
+    // empty when the identifier was never queued
+    pub path: String,
+    pub kind: ResourceErrorKind,
+}
+
+impl std::fmt::Display for ResourceError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match &self.kind {
+            ResourceErrorKind::Load(err) => write!(
+                f,
+                "can't load resource {} from {}: {}",
+                self.identifier, self.path, err
+            ),
+            ResourceErrorKind::Missing => write!(f, "can't find resource: {}", self.identifier),
+            ResourceErrorKind::WrongKind { expected, found } => write!(
+                f,
+                "resource {} from {} is a {:?}, not a {:?}",
+                self.identifier, self.path, found, expected
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ResourceError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match &self.kind {
+            ResourceErrorKind::Load(err) => Some(err.as_ref()),
+            ResourceErrorKind::Missing | ResourceErrorKind::WrongKind { .. } => None,
+        }
+    }
 }
 
 // TextureIdentifier: used as a key to acces the resource
@@ -43,6 +289,58 @@ where
     fn build(builder: &mut ResourceBuilder<ResourceIdentifier, Self, ResourceType, F>) -> Self;
 }
 
+// how a field of a #[derive(Resources)] struct gets its value out of the builder
+// a field of the builder's own resource type is taken as is, AnyResource goes through the typed getters
+pub trait TakeResource<ResourceType>: Sized {
+    fn take_resource<ResourceIdentifier, R, F>(
+        builder: &mut ResourceBuilder<ResourceIdentifier, R, ResourceType, F>,
+        key: ResourceIdentifier,
+    ) -> Result<Self, ResourceError>
+    where
+        ResourceIdentifier: Eq + Hash + Copy + Clone + Debug,
+        R: Resources<ResourceIdentifier, ResourceType, F>,
+        F: ResourceFactory<ResourceType>;
+}
+
+impl<ResourceType> TakeResource<ResourceType> for ResourceType {
+    fn take_resource<ResourceIdentifier, R, F>(
+        builder: &mut ResourceBuilder<ResourceIdentifier, R, ResourceType, F>,
+        key: ResourceIdentifier,
+    ) -> Result<Self, ResourceError>
+    where
+        ResourceIdentifier: Eq + Hash + Copy + Clone + Debug,
+        R: Resources<ResourceIdentifier, ResourceType, F>,
+        F: ResourceFactory<ResourceType>,
+    {
+        builder.try_get(key)
+    }
+}
+
+macro_rules! take_any_resource {
+    ($resource:ty, $getter:ident) => {
+        impl TakeResource<AnyResource> for $resource {
+            fn take_resource<ResourceIdentifier, R, F>(
+                builder: &mut ResourceBuilder<ResourceIdentifier, R, AnyResource, F>,
+                key: ResourceIdentifier,
+            ) -> Result<Self, ResourceError>
+            where
+                ResourceIdentifier: Eq + Hash + Copy + Clone + Debug,
+                R: Resources<ResourceIdentifier, AnyResource, F>,
+                F: ResourceFactory<AnyResource>,
+            {
+                builder.$getter(key)
+            }
+        }
+    };
+}
+
+take_any_resource!(Texture2D, try_get_texture);
+take_any_resource!(Image, try_get_image);
+take_any_resource!(Sound, try_get_sound);
+take_any_resource!(Font, try_get_font);
+take_any_resource!(String, try_get_shader);
+take_any_resource!(Vec<u8>, try_get_bytes);
+
 // R: resources
 pub struct ResourceBuilder<ResourceIdentifier, R, ResourceType, F>
 where
@@ -53,7 +351,24 @@ where
     // path to file
     queued_resources: Vec<(ResourceIdentifier, &'static str)>,
     loaded_resources: HashMap<ResourceIdentifier, ResourceType>,
+    paths: HashMap<ResourceIdentifier, &'static str>,
     total_resources_to_load: i32,
+    use_fallback: bool,
+    // resources that were replaced by the factory fallback
+    fallback_errors: Vec<ResourceError>,
+    // loads kept in flight at once, None loads one file per load_next call
+    concurrency: Option<usize>,
+    in_flight: Vec<(
+        ResourceIdentifier,
+        &'static str,
+        LoadingResource<ResourceType>,
+    )>,
+    // loads that failed in the same load_next call as another one, returned by the following calls
+    finished_errors: VecDeque<ResourceError>,
+    // size in bytes of each resource, progress counts files when empty
+    weights: HashMap<ResourceIdentifier, u64>,
+    // miniquad::date::now() of the first load_next call
+    started_at: Option<f64>,
     phantom_resource_r: PhantomData<R>,
     phantom_resource_f: PhantomData<F>,
 }
@@ -71,49 +386,478 @@ where
     pub fn new(queued_resources: Vec<(TextureIdentifier, &'static str)>) -> Self {
         let total_resources_to_load = queued_resources.len() as i32;
         Self {
+            paths: queued_resources.iter().copied().collect(),
             queued_resources,
             loaded_resources: HashMap::new(),
             total_resources_to_load,
+            use_fallback: false,
+            fallback_errors: Vec::new(),
+            concurrency: None,
+            in_flight: Vec::new(),
+            finished_errors: VecDeque::new(),
+            weights: HashMap::new(),
+            started_at: None,
             phantom_resource_r: PhantomData,
             phantom_resource_f: PhantomData,
         }
     }
 
-    pub async fn load_next(&mut self) -> bool {
-        let is_done = match self.queued_resources.get(0) {
-            Some(identifier_name_pair) => {
-                let resource = F::load_resource(identifier_name_pair.1); //load_texture(identifier_name_pair.1).await;
-                println!("loaded resource: {:?}", identifier_name_pair);
-                self.loaded_resources
-                    .insert(identifier_name_pair.0, resource);
-                false
+    // resources that fail to load are replaced by the factory fallback instead of returning an error
+    // check fallback_errors once loading is done to find out which ones
+    pub fn with_fallback(mut self) -> Self {
+        self.use_fallback = true;
+        self
+    }
+
+    // keeps up to concurrency loads going at once, load_next then polls them without waiting
+    // so the loading screen keeps drawing every frame while files arrive
+    // loads only overlap on wasm, native file loading and decoding finish on their first poll,
+    // so every call starts at most one new load to not stall a frame on several files
+    pub fn with_concurrency(mut self, concurrency: usize) -> Self {
+        self.concurrency = Some(concurrency.max(1));
+        self
+    }
+
+    // declares how many bytes the resource is so progress can weigh it against the others
+    pub fn with_size(mut self, identifier: TextureIdentifier, bytes: u64) -> Self {
+        self.weights.insert(identifier, bytes);
+        self
+    }
+
+    // reads the size of every queued file that has no declared size
+    // wasm can't look at file metadata, there progress keeps counting files
+    pub fn with_file_sizes(mut self) -> Self {
+        #[cfg(not(target_arch = "wasm32"))]
+        for (identifier, path) in self.queued_resources.iter() {
+            if self.weights.contains_key(identifier) {
+                continue;
             }
-            None => true,
-        };
-        match is_done {
-            false => {
+            if let Ok(metadata) = std::fs::metadata(path) {
+                self.weights.insert(*identifier, metadata.len());
+            }
+        }
+        self
+    }
+
+    // loads one queued resource, Ok(true) once there is nothing left to load
+    // a resource that fails is taken off the queue, calling it again continues with the next one
+    // with_concurrency makes every call start and poll loads instead, call it once per frame
+    pub async fn load_next(&mut self) -> Result<bool, ResourceError> {
+        if self.started_at.is_none() {
+            self.started_at = Some(macroquad::miniquad::date::now());
+        }
+        if let Some(err) = self.finished_errors.pop_front() {
+            return Err(err);
+        }
+        let concurrency = match self.concurrency {
+            Some(concurrency) => concurrency,
+            None => {
+                let (identifier, path) = match self.queued_resources.first() {
+                    Some(identifier_path_pair) => *identifier_path_pair,
+                    None => return Ok(true),
+                };
                 let _ = self.queued_resources.remove(0);
+                let result = F::load_resource(path).await;
+                self.finish_load(identifier, path, result).await?;
+                return Ok(false);
+            }
+        };
+
+        if self.in_flight.len() < concurrency && !self.queued_resources.is_empty() {
+            let (identifier, path) = self.queued_resources.remove(0);
+            self.in_flight
+                .push((identifier, path, F::load_resource(path)));
+        }
+        if self.in_flight.is_empty() {
+            return Ok(true);
+        }
+        // poll every load once and return right away, even when none of them is ready yet
+        let in_flight = &mut self.in_flight;
+        let finished = futures::future::poll_fn(|context| {
+            let mut finished = Vec::new();
+            in_flight.retain_mut(|(identifier, path, loading)| {
+                match loading.as_mut().poll(context) {
+                    Poll::Ready(result) => {
+                        finished.push((*identifier, *path, result));
+                        false
+                    }
+                    Poll::Pending => true,
+                }
+            });
+            Poll::Ready(finished)
+        })
+        .await;
+        for (identifier, path, result) in finished {
+            if let Err(err) = self.finish_load(identifier, path, result).await {
+                self.finished_errors.push_back(err);
+            }
+        }
+        match self.finished_errors.pop_front() {
+            Some(err) => Err(err),
+            None => Ok(false),
+        }
+    }
+
+    async fn finish_load(
+        &mut self,
+        identifier: TextureIdentifier,
+        path: &'static str,
+        result: Result<ResourceType, FactoryError>,
+    ) -> Result<(), ResourceError> {
+        let err = match result {
+            Ok(resource) => {
+                println!("loaded resource: {:?}", (identifier, path));
+                self.loaded_resources.insert(identifier, resource);
+                return Ok(());
+            }
+            Err(err) => ResourceError {
+                identifier: format!("{:?}", identifier),
+                path: path.to_string(),
+                kind: ResourceErrorKind::Load(err),
+            },
+        };
+        if self.use_fallback {
+            if let Some(fallback) = F::fallback(path).await {
+                self.loaded_resources.insert(identifier, fallback);
+                self.fallback_errors.push(err);
+                return Ok(());
             }
-            true => {}
         }
-        is_done
+        Err(err)
     }
 
+    // watches every queued file for changes, see ResourceReloader
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn watch(&self) -> ResourceReloader<TextureIdentifier> {
+        ResourceReloader::new(
+            self.paths
+                .iter()
+                .map(|(identifier, path)| (*identifier, *path))
+                .collect(),
+        )
+    }
+
+    // why each resource that got replaced by its fallback failed to load
+    pub fn fallback_errors(&self) -> &[ResourceError] {
+        &self.fallback_errors
+    }
+
+    // from 0 to 1, weighed by size when sizes are known, loads still in flight count as not done
     pub fn progress(&mut self) -> f32 {
-        if self.queued_resources.is_empty() {
-            1f32
-        } else {
-            1. - self.queued_resources.len() as f32 / self.total_resources_to_load as f32
+        let remaining = self.queued_resources.len() + self.in_flight.len();
+        if remaining == 0 {
+            return 1f32;
+        }
+        let total_weight: f64 = self
+            .paths
+            .keys()
+            .map(|identifier| self.weight(identifier))
+            .sum();
+        if total_weight <= 0f64 {
+            return 1. - remaining as f32 / self.total_resources_to_load as f32;
+        }
+        let remaining_weight: f64 = self
+            .queued_resources
+            .iter()
+            .map(|(identifier, _)| identifier)
+            .chain(self.in_flight.iter().map(|(identifier, _, _)| identifier))
+            .map(|identifier| self.weight(identifier))
+            .sum();
+        (1f64 - remaining_weight / total_weight) as f32
+    }
+
+    // resources without a known size weigh as much as the average known one
+    fn weight(&self, identifier: &TextureIdentifier) -> f64 {
+        match self.weights.get(identifier) {
+            Some(bytes) => *bytes as f64,
+            None if self.weights.is_empty() => 0f64,
+            None => self.weights.values().sum::<u64>() as f64 / self.weights.len() as f64,
+        }
+    }
+
+    // seconds left at the speed loading went so far, None before anything finished
+    pub fn estimated_time_remaining(&mut self) -> Option<f32> {
+        let started_at = self.started_at?;
+        let progress = self.progress();
+        if progress <= 0f32 {
+            return None;
         }
+        let elapsed = (macroquad::miniquad::date::now() - started_at) as f32;
+        Some(elapsed * (1f32 - progress) / progress)
     }
 
     pub fn get_or_panic(&mut self, key: TextureIdentifier) -> ResourceType {
+        self.try_get(key).unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    // takes the resource out of the builder
+    pub fn try_get(&mut self, key: TextureIdentifier) -> Result<ResourceType, ResourceError> {
         self.loaded_resources
             .remove(&key)
-            .unwrap_or_else(|| panic!("can't find resource: {:?}", key))
+            .ok_or_else(|| ResourceError {
+                identifier: format!("{:?}", key),
+                path: self.paths.get(&key).unwrap_or(&"").to_string(),
+                kind: ResourceErrorKind::Missing,
+            })
     }
 
     pub fn build(&mut self) -> R {
         R::build(self)
     }
 }
+
+// typed getters for builders loading mixed files, they take the resource out of the builder
+impl<Identifier, R, F> ResourceBuilder<Identifier, R, AnyResource, F>
+where
+    Identifier: Eq + Hash + Copy + Clone + Debug,
+    R: Resources<Identifier, AnyResource, F>,
+    F: ResourceFactory<AnyResource>,
+{
+    fn try_get_kind<T>(
+        &mut self,
+        key: Identifier,
+        expected: ResourceKind,
+        convert: fn(AnyResource) -> Result<T, AnyResource>,
+    ) -> Result<T, ResourceError> {
+        match convert(self.try_get(key)?) {
+            Ok(resource) => Ok(resource),
+            Err(resource) => {
+                let found = resource.kind();
+                self.loaded_resources.insert(key, resource);
+                Err(ResourceError {
+                    identifier: format!("{:?}", key),
+                    path: self.paths.get(&key).unwrap_or(&"").to_string(),
+                    kind: ResourceErrorKind::WrongKind { expected, found },
+                })
+            }
+        }
+    }
+
+    // images get uploaded to the gpu here
+    pub fn try_get_texture(&mut self, key: Identifier) -> Result<Texture2D, ResourceError> {
+        self.try_get_kind(key, ResourceKind::Texture, |resource| match resource {
+            AnyResource::Texture(texture) => Ok(texture),
+            AnyResource::Image(image) => {
+                let texture = Texture2D::from_image(&image);
+                texture.set_filter(FilterMode::Nearest);
+                Ok(texture)
+            }
+            resource => Err(resource),
+        })
+    }
+
+    pub fn try_get_image(&mut self, key: Identifier) -> Result<Image, ResourceError> {
+        self.try_get_kind(key, ResourceKind::Image, |resource| match resource {
+            AnyResource::Image(image) => Ok(image),
+            resource => Err(resource),
+        })
+    }
+
+    pub fn try_get_sound(&mut self, key: Identifier) -> Result<Sound, ResourceError> {
+        self.try_get_kind(key, ResourceKind::Sound, |resource| match resource {
+            AnyResource::Sound(sound) => Ok(sound),
+            resource => Err(resource),
+        })
+    }
+
+    pub fn try_get_font(&mut self, key: Identifier) -> Result<Font, ResourceError> {
+        self.try_get_kind(key, ResourceKind::Font, |resource| match resource {
+            AnyResource::Font(font) => Ok(font),
+            resource => Err(resource),
+        })
+    }
+
+    pub fn try_get_shader(&mut self, key: Identifier) -> Result<String, ResourceError> {
+        self.try_get_kind(key, ResourceKind::Shader, |resource| match resource {
+            AnyResource::Shader(source) => Ok(source),
+            resource => Err(resource),
+        })
+    }
+
+    // any kind of file, the bytes as they were on disk
+    pub fn try_get_bytes(&mut self, key: Identifier) -> Result<Vec<u8>, ResourceError> {
+        self.try_get_kind(key, ResourceKind::Bytes, |resource| match resource {
+            AnyResource::Bytes(bytes) => Ok(bytes),
+            AnyResource::Shader(source) => Ok(source.into_bytes()),
+            resource => Err(resource),
+        })
+    }
+
+    pub fn get_texture_or_panic(&mut self, key: Identifier) -> Texture2D {
+        self.try_get_texture(key)
+            .unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    pub fn get_image_or_panic(&mut self, key: Identifier) -> Image {
+        self.try_get_image(key)
+            .unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    pub fn get_sound_or_panic(&mut self, key: Identifier) -> Sound {
+        self.try_get_sound(key)
+            .unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    pub fn get_font_or_panic(&mut self, key: Identifier) -> Font {
+        self.try_get_font(key)
+            .unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    pub fn get_shader_or_panic(&mut self, key: Identifier) -> String {
+        self.try_get_shader(key)
+            .unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    pub fn get_bytes_or_panic(&mut self, key: Identifier) -> Vec<u8> {
+        self.try_get_bytes(key)
+            .unwrap_or_else(|err| panic!("{}", err))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::executor::block_on;
+
+    // the path is the content, paths ending in "missing" fail to load
+    // every "slow " in the path keeps the load pending for one more poll
+    struct PathFactory;
+
+    #[async_trait]
+    impl ResourceFactory<Vec<u8>> for PathFactory {
+        async fn load_resource(path: &str) -> Result<Vec<u8>, FactoryError> {
+            for _ in path.matches("slow ") {
+                futures::pending!();
+            }
+            if path.ends_with("missing") {
+                return Err("no such file".into());
+            }
+            Ok(path.as_bytes().to_vec())
+        }
+
+        async fn fallback(_path: &str) -> Option<Vec<u8>> {
+            Some(Vec::new())
+        }
+    }
+
+    type PathBuilder =
+        ResourceBuilder<&'static str, ResourceCache<&'static str, Vec<u8>>, Vec<u8>, PathFactory>;
+
+    #[test]
+    fn failed_loads_error_and_continue() {
+        let mut builder = PathBuilder::new(vec![("moose", "missing"), ("tree", "tree")]);
+        let err = block_on(builder.load_next()).unwrap_err();
+        assert_eq!(
+            (err.identifier.as_str(), err.path.as_str()),
+            ("\"moose\"", "missing")
+        );
+        assert!(matches!(err.kind, ResourceErrorKind::Load(_)));
+        assert!(!block_on(builder.load_next()).unwrap());
+        assert!(block_on(builder.load_next()).unwrap());
+        assert!(builder.try_get("moose").is_err());
+    }
+
+    #[test]
+    fn fallback_replaces_failed_loads() {
+        let mut builder =
+            PathBuilder::new(vec![("moose", "missing"), ("tree", "tree")]).with_fallback();
+        while !block_on(builder.load_next()).unwrap() {}
+        assert_eq!(builder.fallback_errors().len(), 1);
+        assert_eq!(builder.fallback_errors()[0].path, "missing");
+        let cache = builder.build();
+        assert_eq!(*cache.get_or_panic(&"moose"), Vec::<u8>::new());
+        assert_eq!(*cache.get_or_panic(&"tree"), b"tree".to_vec());
+    }
+
+    #[test]
+    fn concurrent_loads_return_every_error() {
+        let mut builder = PathBuilder::new(vec![
+            ("tree", "slow tree"),
+            ("moose", "slow slow missing"),
+            ("rock", "slow missing"),
+        ])
+        .with_concurrency(3);
+        let mut results = Vec::new();
+        loop {
+            match block_on(builder.load_next()) {
+                Ok(true) => break,
+                Ok(false) => results.push("loading".to_string()),
+                Err(err) => results.push(err.path),
+            }
+        }
+        // both failing loads finish in the same call, the second error comes out of the next one
+        assert_eq!(
+            results,
+            vec![
+                "loading",
+                "loading",
+                "loading",
+                "slow slow missing",
+                "slow missing"
+            ]
+        );
+        assert_eq!(builder.progress(), 1f32);
+        assert_eq!(
+            *builder.build().get_or_panic(&"tree"),
+            b"slow tree".to_vec()
+        );
+    }
+
+    #[test]
+    fn progress_is_weighed_by_size() {
+        let mut builder = PathBuilder::new(vec![("a", "a"), ("b", "b"), ("c", "c")])
+            .with_size("a", 100)
+            .with_size("b", 300);
+        assert_eq!(builder.progress(), 0f32);
+        // c has no size and weighs as much as the average of a and b
+        let mut progress = Vec::new();
+        while !block_on(builder.load_next()).unwrap() {
+            progress.push(builder.progress());
+        }
+        assert_eq!(progress, vec![1f32 / 6f32, 4f32 / 6f32, 1f32]);
+        assert_eq!(*builder.build().get_or_panic(&"b"), b"b".to_vec());
+    }
+
+    #[test]
+    fn progress_counts_files_without_sizes() {
+        let mut builder = PathBuilder::new(vec![("a", "a"), ("b", "b")]);
+        block_on(builder.load_next()).unwrap();
+        assert_eq!(builder.progress(), 0.5f32);
+    }
+
+    #[test]
+    fn resource_kind_from_extension() {
+        for (path, kind) in [
+            ("examples/resources/moose.png", ResourceKind::Image),
+            ("MOOSE.PNG", ResourceKind::Image),
+            ("moose.tga", ResourceKind::Image),
+            // no decoder for these, see decode_image
+            ("moose.jpg", ResourceKind::Bytes),
+            ("moose.gif", ResourceKind::Bytes),
+            ("sounds/step.ogg", ResourceKind::Sound),
+            ("fonts/pixel.ttf", ResourceKind::Font),
+            ("shaders/water.frag", ResourceKind::Shader),
+            ("levels/1.json", ResourceKind::Bytes),
+            ("LICENSE", ResourceKind::Bytes),
+        ] {
+            assert_eq!(ResourceKind::from_path(path), kind, "{}", path);
+        }
+    }
+
+    #[test]
+    fn decodes_images_without_panicking() {
+        let mut png = Vec::new();
+        ::image::RgbaImage::from_pixel(3, 2, ::image::Rgba([255, 0, 0, 255]))
+            .write_to(
+                &mut std::io::Cursor::new(&mut png),
+                ::image::ImageOutputFormat::Png,
+            )
+            .unwrap();
+        let image = decode_image(&png).unwrap();
+        assert_eq!((image.width, image.height), (3, 2));
+        assert_eq!(&image.bytes[..4], &[255, 0, 0, 255]);
+
+        assert!(decode_image(b"not an image").is_err());
+        assert!(decode_image(&png[..png.len() / 2]).is_err());
+    }
+}
@@ -0,0 +1,71 @@
+use crate::resources::*;
+
+// where a factory gets the bytes of a file, implementing it makes the type a ResourceFactory for
+// textures, images, sounds, raw bytes and AnyResource, decoded the same way whatever the source
+#[async_trait]
+pub trait BytesSource {
+    async fn load_bytes(path: &str) -> Result<Vec<u8>, FactoryError>;
+}
+
+#[async_trait]
+impl<S: BytesSource> ResourceFactory<Texture2D> for S {
+    async fn load_resource(path: &str) -> Result<Texture2D, FactoryError> {
+        let texture = Texture2D::from_image(&decode_image(&S::load_bytes(path).await?)?);
+        texture.set_filter(FilterMode::Nearest);
+        Ok(texture)
+    }
+
+    async fn fallback(path: &str) -> Option<Texture2D> {
+        <DefaultFactory as ResourceFactory<Texture2D>>::fallback(path).await
+    }
+}
+
+#[async_trait]
+impl<S: BytesSource> ResourceFactory<Image> for S {
+    async fn load_resource(path: &str) -> Result<Image, FactoryError> {
+        decode_image(&S::load_bytes(path).await?)
+    }
+
+    async fn fallback(path: &str) -> Option<Image> {
+        <DefaultFactory as ResourceFactory<Image>>::fallback(path).await
+    }
+}
+
+#[async_trait]
+impl<S: BytesSource> ResourceFactory<Sound> for S {
+    async fn load_resource(path: &str) -> Result<Sound, FactoryError> {
+        Ok(load_sound_from_bytes(&S::load_bytes(path).await?).await?)
+    }
+
+    async fn fallback(path: &str) -> Option<Sound> {
+        <DefaultFactory as ResourceFactory<Sound>>::fallback(path).await
+    }
+}
+
+#[async_trait]
+impl<S: BytesSource> ResourceFactory<Vec<u8>> for S {
+    async fn load_resource(path: &str) -> Result<Vec<u8>, FactoryError> {
+        S::load_bytes(path).await
+    }
+}
+
+#[async_trait]
+impl<S: BytesSource> ResourceFactory<AnyResource> for S {
+    async fn load_resource(path: &str) -> Result<AnyResource, FactoryError> {
+        let bytes = S::load_bytes(path).await?;
+        Ok(match ResourceKind::from_path(path) {
+            // from_path never picks Texture
+            ResourceKind::Texture | ResourceKind::Image => {
+                AnyResource::Image(decode_image(&bytes)?)
+            }
+            ResourceKind::Sound => AnyResource::Sound(load_sound_from_bytes(&bytes).await?),
+            ResourceKind::Font => AnyResource::Font(load_ttf_font_from_bytes(&bytes)?),
+            ResourceKind::Shader => AnyResource::Shader(String::from_utf8(bytes)?),
+            ResourceKind::Bytes => AnyResource::Bytes(bytes),
+        })
+    }
+
+    async fn fallback(path: &str) -> Option<AnyResource> {
+        <DefaultFactory as ResourceFactory<AnyResource>>::fallback(path).await
+    }
+}
@@ -0,0 +1,317 @@
+use crate::resources::*;
+use std::sync::Arc;
+
+// cheap to clone, the cache counts how many are alive to know what is still used
+pub type ResourceHandle<T> = Arc<T>;
+
+// frees what the resource holds outside of rust memory when the cache drops it
+pub trait Unload {
+    fn unload(self);
+}
+
+impl Unload for Texture2D {
+    fn unload(self) {
+        self.delete();
+    }
+}
+
+// macroquad can't free sounds, the best we can do is make sure it stops playing
+impl Unload for Sound {
+    fn unload(self) {
+        stop_sound(self);
+    }
+}
+
+// fonts stay in macroquad's font storage until the program exits
+impl Unload for Font {
+    fn unload(self) {}
+}
+
+impl Unload for Image {
+    fn unload(self) {}
+}
+
+impl Unload for String {
+    fn unload(self) {}
+}
+
+impl Unload for Vec<u8> {
+    fn unload(self) {}
+}
+
+impl Unload for AnyResource {
+    fn unload(self) {
+        match self {
+            AnyResource::Texture(texture) => texture.unload(),
+            AnyResource::Sound(sound) => sound.unload(),
+            _ => {}
+        }
+    }
+}
+
+// keeps resources around for as long as the game wants them instead of handing them out once
+// build one with a ResourceBuilder<Id, ResourceCache<Id, T>, T, F>, or fill it with insert
+//
+// Texture2D and Sound are copyable ids, don't keep copies of them around after dropping the
+// handle, an unloaded texture draws garbage
+pub struct ResourceCache<ResourceIdentifier, ResourceType>
+where
+    ResourceIdentifier: Eq + Hash + Clone + Debug,
+{
+    resources: HashMap<ResourceIdentifier, ResourceHandle<ResourceType>>,
+    // handles swapped out by replace that are still held, they share what the cached one holds
+    replaced: HashMap<ResourceIdentifier, Vec<ResourceHandle<ResourceType>>>,
+    paths: HashMap<ResourceIdentifier, &'static str>,
+}
+
+impl<ResourceIdentifier, ResourceType> Default for ResourceCache<ResourceIdentifier, ResourceType>
+where
+    ResourceIdentifier: Eq + Hash + Clone + Debug,
+{
+    fn default() -> Self {
+        Self {
+            resources: HashMap::new(),
+            replaced: HashMap::new(),
+            paths: HashMap::new(),
+        }
+    }
+}
+
+impl<ResourceIdentifier, ResourceType> ResourceCache<ResourceIdentifier, ResourceType>
+where
+    ResourceIdentifier: Eq + Hash + Clone + Debug,
+    ResourceType: Unload,
+{
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // replaces and unloads the resource already cached under identifier, unless it's still used
+    pub fn insert(
+        &mut self,
+        identifier: ResourceIdentifier,
+        resource: ResourceType,
+    ) -> ResourceHandle<ResourceType> {
+        let handle = Arc::new(resource);
+        self.store(identifier, handle.clone());
+        handle
+    }
+
+    // the old resource is only unloaded when no handle to it or to what it replaced is left
+    fn store(&mut self, identifier: ResourceIdentifier, handle: ResourceHandle<ResourceType>) {
+        let unused = self.usage_count(&identifier) == 0;
+        self.replaced.remove(&identifier);
+        if let Some(old) = self.resources.insert(identifier, handle) {
+            if !unused {
+                return;
+            }
+            if let Ok(old) = Arc::try_unwrap(old) {
+                old.unload();
+            }
+        }
+    }
+
+    // swaps the cached resource without unloading the old one, for a new value that shares what
+    // the old one holds, like a texture resized in place
+    // handles to the old one keep counting as uses of the new one
+    pub(crate) fn replace(&mut self, identifier: ResourceIdentifier, resource: ResourceType) {
+        let old = match self
+            .resources
+            .insert(identifier.clone(), Arc::new(resource))
+        {
+            Some(old) => old,
+            None => return,
+        };
+        let replaced = self.replaced.entry(identifier).or_default();
+        replaced.retain(|handle| Arc::strong_count(handle) > 1);
+        if Arc::strong_count(&old) > 1 {
+            replaced.push(old);
+        }
+    }
+
+    // moves everything another cache holds into this one, e.g. the resources of the next level
+    pub fn extend(&mut self, other: Self) {
+        let Self {
+            resources,
+            mut replaced,
+            paths,
+        } = other;
+        self.paths.extend(paths);
+        for (identifier, handle) in resources {
+            self.store(identifier.clone(), handle);
+            if let Some(handles) = replaced.remove(&identifier) {
+                self.replaced.insert(identifier, handles);
+            }
+        }
+    }
+
+    pub fn get(&self, key: &ResourceIdentifier) -> Option<ResourceHandle<ResourceType>> {
+        self.resources.get(key).cloned()
+    }
+
+    pub fn try_get(
+        &self,
+        key: &ResourceIdentifier,
+    ) -> Result<ResourceHandle<ResourceType>, ResourceError> {
+        self.get(key).ok_or_else(|| ResourceError {
+            identifier: format!("{:?}", key),
+            path: self.paths.get(key).unwrap_or(&"").to_string(),
+            kind: ResourceErrorKind::Missing,
+        })
+    }
+
+    pub fn get_or_panic(&self, key: &ResourceIdentifier) -> ResourceHandle<ResourceType> {
+        self.try_get(key).unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    pub fn contains(&self, key: &ResourceIdentifier) -> bool {
+        self.resources.contains_key(key)
+    }
+
+    // handles alive outside of the cache, 0 when unused or not cached
+    pub fn usage_count(&self, key: &ResourceIdentifier) -> usize {
+        self.resources
+            .get(key)
+            .into_iter()
+            .chain(self.replaced.get(key).into_iter().flatten())
+            .map(|handle| Arc::strong_count(handle) - 1)
+            .sum()
+    }
+
+    pub fn len(&self) -> usize {
+        self.resources.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.resources.is_empty()
+    }
+
+    pub fn identifiers(&self) -> impl Iterator<Item = &ResourceIdentifier> {
+        self.resources.keys()
+    }
+
+    // unloads every resource nobody holds a handle to, call it when leaving a level
+    // returns what got unloaded
+    pub fn unload_unused(&mut self) -> Vec<ResourceIdentifier> {
+        let unused: Vec<ResourceIdentifier> = self
+            .resources
+            .keys()
+            .filter(|identifier| self.usage_count(identifier) == 0)
+            .cloned()
+            .collect();
+        for identifier in unused.iter() {
+            self.unload(identifier);
+        }
+        unused
+    }
+
+    // unloads the resource unless handles to it are still alive, those keep it cached
+    // returns false when it's still used
+    pub fn unload(&mut self, key: &ResourceIdentifier) -> bool {
+        if self.usage_count(key) > 0 {
+            return false;
+        }
+        self.paths.remove(key);
+        self.replaced.remove(key);
+        if let Some(Ok(resource)) = self.resources.remove(key).map(Arc::try_unwrap) {
+            resource.unload();
+        }
+        true
+    }
+}
+
+// takes every loaded resource out of the builder
+impl<ResourceIdentifier, ResourceType, F> Resources<ResourceIdentifier, ResourceType, F>
+    for ResourceCache<ResourceIdentifier, ResourceType>
+where
+    ResourceIdentifier: Eq + Hash + Copy + Clone + Debug,
+    ResourceType: Unload,
+    F: ResourceFactory<ResourceType>,
+{
+    fn build(builder: &mut ResourceBuilder<ResourceIdentifier, Self, ResourceType, F>) -> Self {
+        let mut cache = Self::new();
+        for (identifier, resource) in builder.loaded_resources.drain() {
+            if let Some(path) = builder.paths.get(&identifier) {
+                cache.paths.insert(identifier, *path);
+            }
+            cache.insert(identifier, resource);
+        }
+        cache
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cache() -> ResourceCache<&'static str, Vec<u8>> {
+        let mut cache = ResourceCache::new();
+        cache.insert("moose", vec![1, 2, 3]);
+        cache.insert("tree", vec![4]);
+        cache
+    }
+
+    #[test]
+    fn usage_count_follows_handles() {
+        let cache = cache();
+        assert_eq!(cache.usage_count(&"moose"), 0);
+        let handle = cache.get_or_panic(&"moose");
+        let copy = handle.clone();
+        assert_eq!(cache.usage_count(&"moose"), 2);
+        drop(handle);
+        assert_eq!(cache.usage_count(&"moose"), 1);
+        drop(copy);
+        assert_eq!(cache.usage_count(&"moose"), 0);
+        assert_eq!(cache.usage_count(&"missing"), 0);
+    }
+
+    #[test]
+    fn unload_unused_keeps_what_is_held() {
+        let mut cache = cache();
+        let moose = cache.get_or_panic(&"moose");
+        assert_eq!(cache.unload_unused(), vec!["tree"]);
+        assert!(cache.contains(&"moose"));
+        assert!(!cache.contains(&"tree"));
+        assert_eq!(cache.len(), 1);
+
+        assert!(!cache.unload(&"moose"));
+        drop(moose);
+        assert!(cache.unload(&"moose"));
+        assert!(cache.is_empty());
+        assert!(matches!(
+            cache.try_get(&"moose"),
+            Err(ResourceError {
+                kind: ResourceErrorKind::Missing,
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn replaced_values_stay_used_while_old_handles_live() {
+        let mut cache = cache();
+        let old = cache.get_or_panic(&"moose");
+        cache.replace("moose", vec![1, 2, 3, 4]);
+        assert_eq!(cache.usage_count(&"moose"), 1);
+        let new = cache.get_or_panic(&"moose");
+        assert_eq!(cache.usage_count(&"moose"), 2);
+        assert_eq!(cache.unload_unused(), vec!["tree"]);
+        assert!(!cache.unload(&"moose"));
+        drop(new);
+        assert!(!cache.unload(&"moose"));
+        drop(old);
+        assert_eq!(cache.usage_count(&"moose"), 0);
+        assert_eq!(cache.unload_unused(), vec!["moose"]);
+        assert!(cache.is_empty());
+    }
+
+    #[test]
+    fn insert_replaces_but_keeps_handed_out_values() {
+        let mut cache = cache();
+        let old = cache.get_or_panic(&"moose");
+        cache.insert("moose", vec![5]);
+        assert_eq!(*old, vec![1, 2, 3]);
+        assert_eq!(*cache.get_or_panic(&"moose"), vec![5]);
+        assert_eq!(cache.usage_count(&"moose"), 0);
+    }
+}
@@ -0,0 +1,51 @@
+use crate::resources::*;
+use std::collections::BTreeMap;
+use std::sync::RwLock;
+
+// files compiled into the executable, filled by embedded_resources!
+static EMBEDDED_FILES: RwLock<BTreeMap<&'static str, &'static [u8]>> = RwLock::new(BTreeMap::new());
+
+// used by embedded_resources!, files embedded again under the same path replace the old ones
+pub fn embed_files(files: &[(&'static str, &'static [u8])]) {
+    EMBEDDED_FILES
+        .write()
+        .expect("EMBEDDED FILES POISONED")
+        .extend(files.iter().copied());
+}
+
+pub fn embedded_file(path: &str) -> Option<&'static [u8]> {
+    EMBEDDED_FILES
+        .read()
+        .expect("EMBEDDED FILES POISONED")
+        .get(path)
+        .copied()
+}
+
+// reads the files embedded_resources! compiled in, for games shipped as a single executable
+// swap DefaultFactory for it, the Resources implementation stays the same
+pub struct EmbeddedFactory;
+
+#[async_trait]
+impl BytesSource for EmbeddedFactory {
+    async fn load_bytes(path: &str) -> Result<Vec<u8>, FactoryError> {
+        embedded_file(path)
+            .map(|bytes| bytes.to_vec())
+            .ok_or_else(|| format!("{} isn't embedded", path).into())
+    }
+}
+
+// embeds the files at compile time and returns the list ResourceBuilder::new takes
+// paths are relative to the Cargo.toml of the crate using the macro
+//
+// ResourceBuilder::<Id, Textures, Texture2D, EmbeddedFactory>::new(embedded_resources![
+//     (Id::Moose, "examples/resources/moose.png"),
+// ])
+#[macro_export]
+macro_rules! embedded_resources {
+    ($(($identifier:expr, $path:literal)),* $(,)?) => {{
+        $crate::resources::embedded::embed_files(&[
+            $(($path, include_bytes!(concat!(env!("CARGO_MANIFEST_DIR"), "/", $path)) as &'static [u8])),*
+        ]);
+        vec![$(($identifier, $path)),*]
+    }};
+}
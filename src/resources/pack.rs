@@ -0,0 +1,327 @@
+use crate::resources::*;
+use std::convert::TryFrom;
+use std::fmt;
+use std::sync::{Arc, RwLock};
+
+// every file of a directory in one archive, so a release ships a single asset file
+//
+// all numbers are little endian
+// "TTPK", version: u32, file count: u32
+// per file: path length: u32, path: utf8, offset: u64, length: u64
+// file contents, offsets count from the start of the pack
+const MAGIC: &[u8; 4] = b"TTPK";
+const VERSION: u32 = 1;
+
+#[derive(Debug)]
+pub enum PackError {
+    File(FileError),
+    Io(std::io::Error),
+    NotAPack,
+    UnsupportedVersion(u32),
+    // the index points past the end of the pack
+    Truncated,
+    InvalidPath,
+}
+
+impl fmt::Display for PackError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PackError::File(err) => write!(f, "can't load pack: {}", err),
+            PackError::Io(err) => write!(f, "can't read files to pack: {}", err),
+            PackError::NotAPack => write!(f, "not a pack file"),
+            PackError::UnsupportedVersion(version) => {
+                write!(f, "unsupported pack version {}", version)
+            }
+            PackError::Truncated => write!(f, "the pack is truncated"),
+            PackError::InvalidPath => write!(f, "the pack has a path that isn't utf8"),
+        }
+    }
+}
+
+impl std::error::Error for PackError {}
+
+impl From<FileError> for PackError {
+    fn from(err: FileError) -> Self {
+        PackError::File(err)
+    }
+}
+
+impl From<std::io::Error> for PackError {
+    fn from(err: std::io::Error) -> Self {
+        PackError::Io(err)
+    }
+}
+
+pub struct Pack {
+    // path -> (offset, length)
+    entries: HashMap<String, (usize, usize)>,
+    bytes: Vec<u8>,
+}
+
+impl Pack {
+    pub fn from_bytes(bytes: Vec<u8>) -> Result<Self, PackError> {
+        let mut reader = PackReader {
+            bytes: &bytes,
+            position: 0,
+        };
+        if reader.take(4)? != MAGIC {
+            return Err(PackError::NotAPack);
+        }
+        let version = reader.read_u32()?;
+        if version != VERSION {
+            return Err(PackError::UnsupportedVersion(version));
+        }
+        let file_count = reader.read_u32()?;
+        let mut entries = HashMap::new();
+        for _ in 0..file_count {
+            let path_length =
+                usize::try_from(reader.read_u32()?).map_err(|_| PackError::Truncated)?;
+            let path = std::str::from_utf8(reader.take(path_length)?)
+                .map_err(|_| PackError::InvalidPath)?
+                .to_string();
+            let offset = reader.read_usize()?;
+            let length = reader.read_usize()?;
+            if offset
+                .checked_add(length)
+                .filter(|end| *end <= bytes.len())
+                .is_none()
+            {
+                return Err(PackError::Truncated);
+            }
+            entries.insert(path, (offset, length));
+        }
+        Ok(Self { entries, bytes })
+    }
+
+    // files are keyed by the path they had on disk when packed, with / separators
+    pub fn get(&self, path: &str) -> Option<&[u8]> {
+        self.entries
+            .get(normalize_path(path))
+            .map(|(offset, length)| &self.bytes[*offset..*offset + *length])
+    }
+
+    pub fn contains(&self, path: &str) -> bool {
+        self.entries.contains_key(normalize_path(path))
+    }
+
+    pub fn paths(&self) -> impl Iterator<Item = &str> {
+        self.entries.keys().map(|path| path.as_str())
+    }
+}
+
+struct PackReader<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl<'a> PackReader<'a> {
+    fn take(&mut self, length: usize) -> Result<&'a [u8], PackError> {
+        let end = self
+            .position
+            .checked_add(length)
+            .ok_or(PackError::Truncated)?;
+        let bytes = self
+            .bytes
+            .get(self.position..end)
+            .ok_or(PackError::Truncated)?;
+        self.position = end;
+        Ok(bytes)
+    }
+
+    fn read_u32(&mut self) -> Result<u32, PackError> {
+        let mut bytes = [0u8; 4];
+        bytes.copy_from_slice(self.take(4)?);
+        Ok(u32::from_le_bytes(bytes))
+    }
+
+    fn read_u64(&mut self) -> Result<u64, PackError> {
+        let mut bytes = [0u8; 8];
+        bytes.copy_from_slice(self.take(8)?);
+        Ok(u64::from_le_bytes(bytes))
+    }
+
+    // offsets and lengths are u64 on disk, a pack that big can't be addressed on wasm32
+    fn read_usize(&mut self) -> Result<usize, PackError> {
+        usize::try_from(self.read_u64()?).map_err(|_| PackError::Truncated)
+    }
+}
+
+fn normalize_path(path: &str) -> &str {
+    path.trim_start_matches("./")
+}
+
+// files: (path, contents)
+pub fn write_pack(files: &[(String, Vec<u8>)]) -> Vec<u8> {
+    // the index stores normalized paths, its size has to be measured on those
+    let paths: Vec<&str> = files.iter().map(|(path, _)| normalize_path(path)).collect();
+    let index_size: usize = paths.iter().map(|path| 4 + path.len() + 16).sum();
+    let mut offset = 12 + index_size;
+    let mut pack =
+        Vec::with_capacity(offset + files.iter().map(|(_, file)| file.len()).sum::<usize>());
+    pack.extend_from_slice(MAGIC);
+    pack.extend_from_slice(&VERSION.to_le_bytes());
+    pack.extend_from_slice(&(files.len() as u32).to_le_bytes());
+    for (path, (_, file)) in paths.iter().zip(files.iter()) {
+        pack.extend_from_slice(&(path.len() as u32).to_le_bytes());
+        pack.extend_from_slice(path.as_bytes());
+        pack.extend_from_slice(&(offset as u64).to_le_bytes());
+        pack.extend_from_slice(&(file.len() as u64).to_le_bytes());
+        offset += file.len();
+    }
+    for (_, file) in files.iter() {
+        pack.extend_from_slice(file);
+    }
+    pack
+}
+
+// packs every file under directory, keyed by directory joined with the path inside it so
+// "examples/resources" stores "examples/resources/moose.png", the same path the game loads
+#[cfg(not(target_arch = "wasm32"))]
+pub fn pack_directory(directory: &std::path::Path) -> Result<Vec<u8>, PackError> {
+    fn collect(
+        directory: &std::path::Path,
+        files: &mut Vec<(String, Vec<u8>)>,
+    ) -> Result<(), PackError> {
+        for entry in std::fs::read_dir(directory)? {
+            let path = entry?.path();
+            if path.is_dir() {
+                collect(&path, files)?;
+                continue;
+            }
+            let key = path
+                .to_str()
+                .ok_or(PackError::InvalidPath)?
+                .replace('\\', "/");
+            files.push((key, std::fs::read(&path)?));
+        }
+        Ok(())
+    }
+    let mut files = Vec::new();
+    collect(directory, &mut files)?;
+    // same input, same pack
+    files.sort_by(|a, b| a.0.cmp(&b.0));
+    Ok(write_pack(&files))
+}
+
+// packs mounted later are searched first, so a patch pack can override files of the base one
+static MOUNTED_PACKS: RwLock<Vec<Arc<Pack>>> = RwLock::new(Vec::new());
+
+pub fn mount_pack(pack: Pack) {
+    MOUNTED_PACKS
+        .write()
+        .expect("PACKS POISONED")
+        .push(Arc::new(pack));
+}
+
+// loads a pack through macroquad so it works on wasm too
+pub async fn load_pack(path: &str) -> Result<(), PackError> {
+    mount_pack(Pack::from_bytes(load_file(path).await?)?);
+    Ok(())
+}
+
+pub fn unmount_packs() {
+    MOUNTED_PACKS.write().expect("PACKS POISONED").clear();
+}
+
+// debug builds read loose files that aren't in a mounted pack, so nothing has to be packed
+// while working on the game, release builds only read packs
+pub async fn load_pack_file(path: &str) -> Result<Vec<u8>, FactoryError> {
+    let pack = MOUNTED_PACKS
+        .read()
+        .expect("PACKS POISONED")
+        .iter()
+        .rev()
+        .find(|pack| pack.contains(path))
+        .cloned();
+    if let Some(pack) = pack {
+        return Ok(pack.get(path).expect("PACK ENTRY").to_vec());
+    }
+    if cfg!(debug_assertions) {
+        return Ok(load_file(path).await?);
+    }
+    Err(format!("{} isn't in any mounted pack", path).into())
+}
+
+// reads resources out of the mounted packs, see load_pack
+pub struct PackFactory;
+
+#[async_trait]
+impl BytesSource for PackFactory {
+    async fn load_bytes(path: &str) -> Result<Vec<u8>, FactoryError> {
+        load_pack_file(path).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn files() -> Vec<(String, Vec<u8>)> {
+        vec![
+            ("./assets/moose.png".to_string(), vec![1, 2, 3]),
+            ("assets/moose.json".to_string(), b"{}".to_vec()),
+            ("./empty".to_string(), Vec::new()),
+        ]
+    }
+
+    #[test]
+    fn round_trip() {
+        let pack = Pack::from_bytes(write_pack(&files())).unwrap();
+        let mut paths: Vec<&str> = pack.paths().collect();
+        paths.sort_unstable();
+        assert_eq!(
+            paths,
+            vec!["assets/moose.json", "assets/moose.png", "empty"]
+        );
+        assert_eq!(pack.get("assets/moose.png"), Some(&[1u8, 2, 3][..]));
+        assert_eq!(pack.get("./assets/moose.png"), Some(&[1u8, 2, 3][..]));
+        assert_eq!(pack.get("assets/moose.json"), Some(&b"{}"[..]));
+        assert_eq!(pack.get("empty"), Some(&[][..]));
+        assert_eq!(pack.get("assets/missing.png"), None);
+    }
+
+    #[test]
+    fn rejects_other_files() {
+        assert!(matches!(
+            Pack::from_bytes(b"PNG and more".to_vec()),
+            Err(PackError::NotAPack)
+        ));
+        let mut bytes = write_pack(&files());
+        bytes[4] = 2;
+        assert!(matches!(
+            Pack::from_bytes(bytes),
+            Err(PackError::UnsupportedVersion(2))
+        ));
+    }
+
+    #[test]
+    fn truncated_pack() {
+        let bytes = write_pack(&files());
+        for length in [0, 6, 20, bytes.len() - 1] {
+            assert!(
+                Pack::from_bytes(bytes[..length].to_vec()).is_err(),
+                "{} bytes",
+                length
+            );
+        }
+    }
+
+    #[test]
+    fn huge_lengths_in_the_index() {
+        let mut bytes = MAGIC.to_vec();
+        bytes.extend_from_slice(&VERSION.to_le_bytes());
+        bytes.extend_from_slice(&1u32.to_le_bytes());
+        let mut path_too_long = bytes.clone();
+        path_too_long.extend_from_slice(&u32::MAX.to_le_bytes());
+        assert!(matches!(
+            Pack::from_bytes(path_too_long),
+            Err(PackError::Truncated)
+        ));
+
+        bytes.extend_from_slice(&1u32.to_le_bytes());
+        bytes.push(b'a');
+        bytes.extend_from_slice(&u64::MAX.to_le_bytes());
+        bytes.extend_from_slice(&1u64.to_le_bytes());
+        assert!(matches!(Pack::from_bytes(bytes), Err(PackError::Truncated)));
+    }
+}
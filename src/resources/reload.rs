@@ -0,0 +1,148 @@
+use crate::resources::*;
+use std::time::SystemTime;
+
+// opt in reloading for development, polls the modified time of every watched file
+// get one from ResourceBuilder::watch and poll it once per frame
+//
+// textures are updated in place so every handle and copy sees the new pixels, a texture that
+// changed size keeps its old width and height in handles fetched before, fetch it again to draw
+// it at the new size
+// sounds can't be changed in place, the cache gets a new one and the old is unloaded once unused,
+// use the returned identifiers to fetch it again
+pub struct ResourceReloader<ResourceIdentifier> {
+    watched: Vec<(ResourceIdentifier, &'static str, Option<SystemTime>)>,
+    // failed reloads, see texture_errors and sound_errors
+    texture_errors: Vec<ResourceError>,
+    sound_errors: Vec<ResourceError>,
+}
+
+impl<ResourceIdentifier> ResourceReloader<ResourceIdentifier>
+where
+    ResourceIdentifier: Eq + Hash + Copy + Clone + Debug,
+{
+    pub fn new(paths: Vec<(ResourceIdentifier, &'static str)>) -> Self {
+        Self {
+            watched: paths
+                .into_iter()
+                .map(|(identifier, path)| (identifier, path, modified_time(path)))
+                .collect(),
+            texture_errors: Vec::new(),
+            sound_errors: Vec::new(),
+        }
+    }
+
+    // takes the files that changed since the last poll, remembering their new modified time
+    // only files the cache holds are looked at, the others are picked up once they are cached
+    fn changed_files<ResourceType>(
+        &mut self,
+        cache: &ResourceCache<ResourceIdentifier, ResourceType>,
+    ) -> Vec<(ResourceIdentifier, &'static str)>
+    where
+        ResourceType: Unload,
+    {
+        let mut changed = Vec::new();
+        for (identifier, path, modified) in self.watched.iter_mut() {
+            if !cache.contains(identifier) {
+                continue;
+            }
+            let new_modified = modified_time(path);
+            if new_modified != *modified {
+                *modified = new_modified;
+                changed.push((*identifier, *path));
+            }
+        }
+        changed
+    }
+
+    // returns the identifiers of the textures that got new pixels
+    // a file that fails to load keeps its old pixels and is tried again when saved again,
+    // its error is kept for texture_errors
+    pub fn poll_textures(
+        &mut self,
+        cache: &mut ResourceCache<ResourceIdentifier, Texture2D>,
+    ) -> Vec<ResourceIdentifier> {
+        let mut reloaded = Vec::new();
+        for (identifier, path) in self.changed_files(cache) {
+            let image = std::fs::read(path)
+                .map_err(FactoryError::from)
+                .and_then(|bytes| decode_image(&bytes));
+            let image = match image {
+                Ok(image) => image,
+                Err(err) => {
+                    self.texture_errors.push(load_error(identifier, path, err));
+                    continue;
+                }
+            };
+            let texture = cache.get_or_panic(&identifier);
+            if texture.width() as u16 == image.width && texture.height() as u16 == image.height {
+                texture.update(&image);
+            } else {
+                // same gpu texture with a new size, so nothing leaks and copies draw the new pixels
+                let mut raw_texture = texture.raw_miniquad_texture_handle();
+                let gl = unsafe { get_internal_gl() };
+                raw_texture.resize(
+                    gl.quad_context,
+                    image.width as u32,
+                    image.height as u32,
+                    Some(&image.bytes),
+                );
+                cache.replace(identifier, Texture2D::from_miniquad_texture(raw_texture));
+            }
+            reloaded.push(identifier);
+        }
+        reloaded
+    }
+
+    // returns the identifiers of the sounds that got replaced in the cache,
+    // failed files are kept for sound_errors
+    pub async fn poll_sounds(
+        &mut self,
+        cache: &mut ResourceCache<ResourceIdentifier, Sound>,
+    ) -> Vec<ResourceIdentifier> {
+        let mut reloaded = Vec::new();
+        for (identifier, path) in self.changed_files(cache) {
+            let sound = match std::fs::read(path) {
+                Ok(bytes) => load_sound_from_bytes(&bytes)
+                    .await
+                    .map_err(FactoryError::from),
+                Err(err) => Err(err.into()),
+            };
+            match sound {
+                Ok(sound) => {
+                    cache.insert(identifier, sound);
+                    reloaded.push(identifier);
+                }
+                Err(err) => self.sound_errors.push(load_error(identifier, path, err)),
+            }
+        }
+        reloaded
+    }
+
+    // why textures failed to reload since the last call
+    pub fn texture_errors(&mut self) -> std::vec::Drain<'_, ResourceError> {
+        self.texture_errors.drain(..)
+    }
+
+    // why sounds failed to reload since the last call
+    pub fn sound_errors(&mut self) -> std::vec::Drain<'_, ResourceError> {
+        self.sound_errors.drain(..)
+    }
+}
+
+pub(crate) fn modified_time(path: &str) -> Option<SystemTime> {
+    std::fs::metadata(path)
+        .and_then(|metadata| metadata.modified())
+        .ok()
+}
+
+fn load_error<ResourceIdentifier: Debug>(
+    identifier: ResourceIdentifier,
+    path: &str,
+    err: FactoryError,
+) -> ResourceError {
+    ResourceError {
+        identifier: format!("{:?}", identifier),
+        path: path.to_string(),
+        kind: ResourceErrorKind::Load(err),
+    }
+}